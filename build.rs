@@ -1,5 +1,134 @@
+use std::env;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+/// Unicode blocks that may be named in `ORBCLIENT_UNIFONT_RANGES`
+const BLOCKS: &[(&str, u32, u32)] = &[
+    ("basic-latin", 0x0000, 0x007F),
+    ("latin-1", 0x0080, 0x00FF),
+    ("latin-extended-a", 0x0100, 0x017F),
+    ("latin-extended-b", 0x0180, 0x024F),
+    ("combining", 0x0300, 0x036F),
+    ("greek", 0x0370, 0x03FF),
+    ("cyrillic", 0x0400, 0x04FF),
+    ("general-punctuation", 0x2000, 0x206F),
+    ("arrows", 0x2190, 0x21FF),
+    ("math", 0x2200, 0x22FF),
+    ("box-drawing", 0x2500, 0x257F),
+    ("block-elements", 0x2580, 0x259F),
+    ("geometric-shapes", 0x25A0, 0x25FF),
+];
+
+/// Subset used when `ORBCLIENT_UNIFONT_RANGES` is not set
+const DEFAULT_RANGES: &str = "basic-latin,latin-1,combining,box-drawing,block-elements";
+
+/// Always embedded so missing characters have something to fall back to
+const REPLACEMENT: u32 = 0xFFFD;
+
 fn main() {
-    if std::env::var("CARGO_CFG_TARGET_OS") == Ok("macos".to_string()) {
+    println!("cargo:rerun-if-changed=build.rs");
+
+    if env::var("CARGO_CFG_TARGET_OS") == Ok("macos".to_string()) {
         println!("cargo:rustc-link-lib=framework=CoreHaptics");
     }
+
+    // The full font takes precedence, so only generate a subset when it is the sole font
+    if env::var_os("CARGO_FEATURE_UNIFONT_SUBSET").is_some()
+        && env::var_os("CARGO_FEATURE_UNIFONT").is_none()
+    {
+        unifont_subset();
+    }
+}
+
+fn parse_ranges(spec: &str) -> Vec<(u32, u32)> {
+    let mut ranges = Vec::new();
+    for part in spec
+        .split(',')
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+    {
+        if let Some(&(_, start, end)) = BLOCKS.iter().find(|(name, _, _)| *name == part) {
+            ranges.push((start, end));
+            continue;
+        }
+
+        let parse = |s: &str| {
+            let s = s.trim().trim_start_matches("U+").trim_start_matches("u+");
+            u32::from_str_radix(s, 16)
+                .unwrap_or_else(|_| panic!("invalid codepoint {:?} in unifont range {:?}", s, part))
+        };
+        match part.split_once('-') {
+            Some((start, end)) => ranges.push((parse(start), parse(end))),
+            None => {
+                let c = parse(part);
+                ranges.push((c, c));
+            }
+        }
+    }
+    ranges.push((REPLACEMENT, REPLACEMENT));
+
+    // Sort and merge so the generated index can be binary searched
+    ranges.sort_unstable();
+    let mut merged: Vec<(u32, u32)> = Vec::with_capacity(ranges.len());
+    for (start, end) in ranges {
+        assert!(
+            start <= end,
+            "unifont range {:X}-{:X} is reversed",
+            start,
+            end
+        );
+        match merged.last_mut() {
+            Some(last) if start <= last.1 + 1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+fn unifont_subset() {
+    println!("cargo:rerun-if-changed=res/unifont.font");
+    println!("cargo:rerun-if-changed=res/unifont.defined");
+    println!("cargo:rerun-if-env-changed=ORBCLIENT_UNIFONT_RANGES");
+
+    let spec = env::var("ORBCLIENT_UNIFONT_RANGES").unwrap_or_else(|_| DEFAULT_RANGES.to_string());
+    let font = fs::read("res/unifont.font").expect("failed to read res/unifont.font");
+    let font_glyphs = (font.len() / 16) as u32;
+    // The font stores missing glyphs as empty bitmaps just like blank ones, so a bitmap
+    // generated along with it by res/unifont.rs tells them apart
+    let defined = fs::read("res/unifont.defined").expect("failed to read res/unifont.defined");
+    let is_defined = |c: u32| {
+        defined
+            .get(c as usize / 8)
+            .is_some_and(|byte| byte & (1 << (c % 8)) != 0)
+    };
+
+    let mut glyphs = Vec::new();
+    let mut index = String::from("pub(super) static RANGES: &[(u32, u32, u32)] = &[\n");
+    let mut count = 0;
+    for (start, end) in parse_ranges(&spec) {
+        // Only glyphs the font has go in the index, split into runs of consecutive ones
+        let end = end.min(font_glyphs.saturating_sub(1));
+        let mut c = start;
+        while c <= end {
+            if !is_defined(c) {
+                c += 1;
+                continue;
+            }
+            let run_start = c;
+            while c < end && is_defined(c + 1) {
+                c += 1;
+            }
+            glyphs.extend_from_slice(&font[run_start as usize * 16..(c as usize + 1) * 16]);
+            writeln!(index, "    (0x{:04X}, 0x{:04X}, {}),", run_start, c, count).unwrap();
+            count += c - run_start + 1;
+            c += 1;
+        }
+    }
+    index.push_str("];\n");
+
+    let out_dir = env::var("OUT_DIR").unwrap();
+    let out_dir = Path::new(&out_dir);
+    fs::write(out_dir.join("unifont-subset.font"), glyphs).unwrap();
+    fs::write(out_dir.join("unifont-subset.rs"), index).unwrap();
 }
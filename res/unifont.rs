@@ -6,6 +6,8 @@ use std::io::{BufRead, BufReader, Write};
 fn main() {
     let input = File::open("unifont.hex").unwrap();
     let mut output = File::create("unifont.font").unwrap();
+    // One bit per codepoint, set for those the font has, as missing ones are stored blank
+    let mut defined = [0u8; 0x10000 / 8];
 
     let mut count = 0;
     for line_res in BufReader::new(input).lines() {
@@ -30,6 +32,9 @@ fn main() {
         println!("{:>04X}:{:?}", num, data);
 
         output.write(&data).unwrap();
+        defined[num as usize / 8] |= 1 << (num % 8);
         count += 1;
     }
+
+    File::create("unifont.defined").unwrap().write(&defined).unwrap();
 }
@@ -0,0 +1,410 @@
+// SPDX-License-Identifier: MIT
+
+//! Animated images, made of full frames shown one after another.
+//!
+//! With the `image` feature, animated GIF, APNG and WebP files can be loaded with
+//! [`AnimatedImage::from_path`] and [`AnimatedImage::from_memory`].
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+use core::time::Duration;
+
+use crate::color::Color;
+use crate::image::Image;
+use crate::rect::Rect;
+use crate::renderer::Renderer;
+
+#[cfg(feature = "image")]
+use crate::image::ImageError;
+#[cfg(feature = "image")]
+use std::path::Path;
+
+/// GIF frame delays up to this are treated as [`SHORT_DELAY_REPLACEMENT`], like web browsers do
+#[cfg(feature = "image")]
+const SHORT_DELAY: Duration = Duration::from_millis(10);
+#[cfg(feature = "image")]
+const SHORT_DELAY_REPLACEMENT: Duration = Duration::from_millis(100);
+
+/// What happens to the area of a frame before the next frame is drawn
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Disposal {
+    /// Leave the frame in place
+    #[default]
+    None,
+    /// Clear the area of the frame to transparent
+    Background,
+    /// Restore the area to what it was before the frame was drawn
+    Previous,
+}
+
+/// How a frame is combined with the canvas
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Blend {
+    /// Replace the pixels of the canvas, including their alpha
+    #[default]
+    Source,
+    /// Alpha blend the frame over the canvas
+    Over,
+}
+
+/// How many times an animation plays
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LoopCount {
+    Forever,
+    Times(u32),
+}
+
+/// A partial frame, as stored in animated files
+#[derive(Clone)]
+pub struct Frame {
+    pub image: Image,
+    /// Position of the frame on the canvas
+    pub x: i32,
+    pub y: i32,
+    /// How long the frame is shown
+    pub delay: Duration,
+    pub disposal: Disposal,
+    pub blend: Blend,
+}
+
+/// An animation of full canvas sized frames with their delays
+#[derive(Clone)]
+pub struct AnimatedImage {
+    w: u32,
+    h: u32,
+    frames: Vec<Image>,
+    delays: Vec<Duration>,
+    loop_count: LoopCount,
+    /// The canvas the next pushed frame is drawn on, after disposal of the last one
+    canvas: Image,
+}
+
+impl AnimatedImage {
+    /// Create an animation without frames on a transparent canvas
+    pub fn new(width: u32, height: u32, loop_count: LoopCount) -> Self {
+        AnimatedImage {
+            w: width,
+            h: height,
+            frames: Vec::new(),
+            delays: Vec::new(),
+            loop_count,
+            canvas: Image::from_color(width, height, Color::rgba(0, 0, 0, 0)),
+        }
+    }
+
+    /// Create an animation with a single frame
+    pub fn from_image(image: Image) -> Self {
+        let mut animation = Self::new(image.width(), image.height(), LoopCount::Times(1));
+        animation.canvas = image.clone();
+        animation.frames.push(image);
+        animation.delays.push(Duration::ZERO);
+        animation
+    }
+
+    /// Compose a frame onto the canvas and add the result, then apply the frame's disposal
+    pub fn push(&mut self, frame: Frame) {
+        let canvas = Rect::new(0, 0, self.w, self.h);
+        let area = Rect::new(frame.x, frame.y, frame.image.width(), frame.image.height());
+        let dst = canvas.intersection(&area);
+        let src = dst.translate(-frame.x, -frame.y);
+
+        let previous = match frame.disposal {
+            Disposal::Previous => Some(self.canvas.clone()),
+            _ => None,
+        };
+        if !dst.is_empty() {
+            let mut roi = self.canvas.roi_mut(&dst);
+            match frame.blend {
+                Blend::Source => roi.blit(&frame.image.roi(&src)),
+                Blend::Over => roi.blend(&frame.image.roi(&src)),
+            }
+        }
+        self.frames.push(self.canvas.clone());
+        self.delays.push(frame.delay);
+
+        match frame.disposal {
+            Disposal::None => (),
+            Disposal::Background => {
+                if !dst.is_empty() {
+                    for row in self.canvas.roi_mut(&dst).rows_mut() {
+                        row.fill(Color::rgba(0, 0, 0, 0));
+                    }
+                }
+            }
+            Disposal::Previous => {
+                if let Some(previous) = previous {
+                    self.canvas = previous;
+                }
+            }
+        }
+    }
+
+    /// Add a frame that already covers the whole canvas
+    pub fn push_full(&mut self, image: Image, delay: Duration) {
+        self.push(Frame {
+            image,
+            x: 0,
+            y: 0,
+            delay,
+            disposal: Disposal::None,
+            blend: Blend::Source,
+        });
+    }
+
+    /// Load an animated GIF, APNG or WebP file, other images become a single frame
+    #[cfg(feature = "image")]
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, ImageError> {
+        let data = std::fs::read(path).map_err(ImageError::IoError)?;
+        Self::from_memory(&data)
+    }
+
+    /// Load an animated GIF, APNG or WebP file in memory, other images become a single frame
+    #[cfg(feature = "image")]
+    pub fn from_memory(data: &[u8]) -> Result<Self, ImageError> {
+        use image::codecs::{gif::GifDecoder, png::PngDecoder, webp::WebPDecoder};
+        use image::ImageFormat;
+        use std::io::Cursor;
+
+        let format = image::guess_format(data)?;
+        match format {
+            ImageFormat::Gif => Self::from_decoder(GifDecoder::new(Cursor::new(data))?, true),
+            ImageFormat::Png => {
+                let decoder = PngDecoder::new(Cursor::new(data))?;
+                if decoder.is_apng()? {
+                    Self::from_decoder(decoder.apng()?, false)
+                } else {
+                    Image::from_memory_with_format(data, format).map(Self::from_image)
+                }
+            }
+            ImageFormat::WebP => {
+                let decoder = WebPDecoder::new(Cursor::new(data))?;
+                if decoder.has_animation() {
+                    Self::from_decoder(decoder, false)
+                } else {
+                    Image::from_memory_with_format(data, format).map(Self::from_image)
+                }
+            }
+            _ => Image::from_memory_with_format(data, format).map(Self::from_image),
+        }
+    }
+
+    /// Collect the frames of a decoder, which are already composed to the full canvas.
+    /// With `gif_delays`, very short delays are lengthened as GIF viewers do.
+    #[cfg(feature = "image")]
+    fn from_decoder<'a, D: image::AnimationDecoder<'a>>(
+        decoder: D,
+        gif_delays: bool,
+    ) -> Result<Self, ImageError> {
+        let loop_count = match decoder.loop_count() {
+            image::metadata::LoopCount::Infinite => LoopCount::Forever,
+            image::metadata::LoopCount::Finite(n) => LoopCount::Times(n.get()),
+        };
+        let mut animation: Option<Self> = None;
+        for frame in decoder.into_frames() {
+            let frame = frame?;
+            let (numer, denom) = frame.delay().numer_denom_ms();
+            let mut delay = Duration::from_micros(numer as u64 * 1000 / denom.max(1) as u64);
+            if gif_delays && delay <= SHORT_DELAY {
+                delay = SHORT_DELAY_REPLACEMENT;
+            }
+            let (left, top) = (frame.left() as i32, frame.top() as i32);
+            let buffer = frame.into_buffer();
+            let data: Vec<Color> = buffer
+                .pixels()
+                .map(|p| Color::rgba(p[0], p[1], p[2], p[3]))
+                .collect();
+            let image = Image::from_data(buffer.width(), buffer.height(), data.into_boxed_slice())
+                .expect("frame buffer size matches its dimensions");
+
+            let animation = animation.get_or_insert_with(|| {
+                Self::new(
+                    left as u32 + image.width(),
+                    top as u32 + image.height(),
+                    loop_count,
+                )
+            });
+            animation.push(Frame {
+                image,
+                x: left,
+                y: top,
+                delay,
+                disposal: Disposal::None,
+                blend: Blend::Source,
+            });
+        }
+        animation.ok_or_else(|| {
+            use image::error::{ParameterError, ParameterErrorKind};
+            ImageError::Parameter(ParameterError::from_kind(ParameterErrorKind::NoMoreData))
+        })
+    }
+
+    pub fn width(&self) -> u32 {
+        self.w
+    }
+
+    pub fn height(&self) -> u32 {
+        self.h
+    }
+
+    pub fn loop_count(&self) -> LoopCount {
+        self.loop_count
+    }
+
+    pub fn set_loop_count(&mut self, loop_count: LoopCount) {
+        self.loop_count = loop_count;
+    }
+
+    /// The composed frames, each the size of the canvas
+    pub fn frames(&self) -> &[Image] {
+        &self.frames
+    }
+
+    /// How long each frame is shown
+    pub fn delays(&self) -> &[Duration] {
+        &self.delays
+    }
+
+    /// Duration of one loop of the animation
+    pub fn duration(&self) -> Duration {
+        self.delays.iter().sum()
+    }
+
+    /// Index of the frame to show after `elapsed` time since the start.
+    /// Stays on the last frame once all loops are done. `None` if there are no frames.
+    pub fn frame_index_at(&self, elapsed: Duration) -> Option<usize> {
+        let last = self.frames.len().checked_sub(1)?;
+        let total = self.duration().as_nanos();
+        if total == 0 {
+            return Some(last);
+        }
+        let elapsed = elapsed.as_nanos();
+        if let LoopCount::Times(times) = self.loop_count {
+            if elapsed >= total * times.max(1) as u128 {
+                return Some(last);
+            }
+        }
+
+        let mut time = elapsed % total;
+        for (i, delay) in self.delays.iter().enumerate() {
+            let delay = delay.as_nanos();
+            if time < delay {
+                return Some(i);
+            }
+            time -= delay;
+        }
+        Some(last)
+    }
+
+    /// The frame to show after `elapsed` time since the start
+    pub fn frame_at(&self, elapsed: Duration) -> Option<&Image> {
+        self.frame_index_at(elapsed).map(|i| &self.frames[i])
+    }
+}
+
+/// Plays an animation by tracking the elapsed time
+pub struct Player<'a> {
+    animation: &'a AnimatedImage,
+    elapsed: Duration,
+    index: usize,
+}
+
+impl<'a> Player<'a> {
+    pub fn new(animation: &'a AnimatedImage) -> Self {
+        Player {
+            animation,
+            elapsed: Duration::ZERO,
+            index: 0,
+        }
+    }
+
+    /// Move the animation forward, returns true if a different frame should be shown
+    pub fn advance(&mut self, time: Duration) -> bool {
+        self.elapsed += time;
+        let index = self.animation.frame_index_at(self.elapsed).unwrap_or(0);
+        let changed = index != self.index;
+        self.index = index;
+        changed
+    }
+
+    /// Go back to the first frame
+    pub fn reset(&mut self) {
+        self.elapsed = Duration::ZERO;
+        self.index = 0;
+    }
+
+    /// Time played since the start
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Index of the current frame
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// The current frame, `None` if the animation has no frames
+    pub fn frame(&self) -> Option<&'a Image> {
+        self.animation.frames().get(self.index)
+    }
+
+    /// True when all loops have been played
+    pub fn is_finished(&self) -> bool {
+        match self.animation.loop_count() {
+            LoopCount::Forever => false,
+            LoopCount::Times(times) => {
+                self.elapsed.as_nanos()
+                    >= self.animation.duration().as_nanos() * times.max(1) as u128
+            }
+        }
+    }
+
+    /// Time until the next frame should be shown, `None` once finished or for a single frame,
+    /// for use as a timer
+    pub fn time_to_next_frame(&self) -> Option<Duration> {
+        if self.animation.frames().len() < 2 || self.is_finished() {
+            return None;
+        }
+        let total = self.animation.duration().as_nanos();
+        let mut time = self.elapsed.as_nanos() % total;
+        for delay in self.animation.delays() {
+            let delay = delay.as_nanos();
+            if time < delay {
+                return Some(Duration::from_nanos((delay - time) as u64));
+            }
+            time -= delay;
+        }
+        None
+    }
+
+    /// Draw the current frame on a renderer
+    pub fn draw<R: Renderer + ?Sized>(&self, renderer: &mut R, x: i32, y: i32) {
+        if let Some(frame) = self.frame() {
+            frame.draw(renderer, x, y);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "image"))]
+mod tests {
+    use super::*;
+    use image::codecs::gif::GifEncoder;
+    use image::{Delay, RgbaImage};
+
+    #[test]
+    fn gif_short_delays() {
+        let mut data = Vec::new();
+        {
+            let mut encoder = GifEncoder::new(&mut data);
+            for ms in [0, 10, 20, 50] {
+                let buffer = RgbaImage::from_pixel(2, 2, image::Rgba([ms as u8, 0, 0, 255]));
+                let delay = Delay::from_numer_denom_ms(ms, 1);
+                encoder
+                    .encode_frame(image::Frame::from_parts(buffer, 0, 0, delay))
+                    .unwrap();
+            }
+        }
+        let animation = AnimatedImage::from_memory(&data).unwrap();
+        let ms = |ms| Duration::from_millis(ms);
+        assert_eq!(animation.delays(), &[ms(100), ms(100), ms(20), ms(50)]);
+    }
+}
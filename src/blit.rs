@@ -0,0 +1,176 @@
+// SPDX-License-Identifier: MIT
+
+//! Copying and blending images onto renderers, clipped on every edge.
+
+use crate::color::Color;
+use crate::parallel;
+use crate::rect::Rect;
+use crate::renderer::Renderer;
+use crate::simd;
+use crate::Mode;
+
+/// Draw the pixels of `src`, whose rows start `stride` pixels apart, into `dest`. Only the part
+/// of `dest` inside the renderer is drawn, so images may hang off any edge, and rows are cut
+/// short where `src` ends. `Mode::Overwrite` copies the pixels and `Mode::Blend` blends them
+/// by their alpha.
+pub(crate) fn blit<R: Renderer + ?Sized>(
+    renderer: &mut R,
+    dest: Rect,
+    src: &[Color],
+    stride: usize,
+    mode: Mode,
+) {
+    let width = renderer.stride();
+    let visible = dest.intersection(&Rect::new(0, 0, renderer.width(), renderer.height()));
+    if visible.is_empty() {
+        return;
+    }
+    // Pixels of the image left of and above the renderer
+    let skip_x = (visible.left() - dest.left()) as usize;
+    let skip_y = (visible.top() - dest.top()) as usize;
+    let left = visible.left() as usize;
+    let top = visible.top() as usize;
+    let row_w = visible.width() as usize;
+
+    let end = (top + visible.height() as usize - 1) * width + left + row_w;
+    let rows = &mut renderer.data_mut()[top * width..end];
+    parallel::for_each_band(rows, width, |y0, band| {
+        for (l, row) in band.chunks_mut(width).enumerate() {
+            let start = (skip_y + y0 + l) * stride + skip_x;
+            let source = src.get(start..).unwrap_or(&[]);
+            let n = row_w.min(source.len());
+            let target = &mut row[left..left + n];
+            match mode {
+                Mode::Overwrite => target.copy_from_slice(&source[..n]),
+                Mode::Blend => simd::blend(target, &source[..n]),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(not(feature = "std"))]
+    use alloc::vec::Vec;
+
+    use super::*;
+    use crate::image::Image;
+
+    const W: u32 = 8;
+    const H: u32 = 6;
+
+    /// Source pixels with a mix of opaque, transparent and translucent alpha
+    fn source(len: usize) -> Vec<Color> {
+        (0..len as u32)
+            .map(|i| {
+                let alpha = [255, 0, 128, 37][i as usize % 4];
+                Color::rgba((i * 37) as u8, (i * 91) as u8, (i * 13) as u8, alpha)
+            })
+            .collect()
+    }
+
+    /// What `blit` should do, one pixel at a time
+    fn reference(image: &mut Image, dest: Rect, src: &[Color], stride: usize, mode: Mode) {
+        let (w, data) = (image.width() as i32, image.data_mut());
+        for y in dest.top().max(0)..dest.bottom().min(H as i32) {
+            for x in dest.left().max(0)..dest.right().min(W as i32) {
+                let i = (y - dest.top()) as usize * stride + (x - dest.left()) as usize;
+                let Some(&color) = src.get(i) else {
+                    continue;
+                };
+                let pixel = &mut data[(y * w + x) as usize..][..1];
+                match mode {
+                    Mode::Overwrite => pixel[0] = color,
+                    Mode::Blend => simd::blend(pixel, &[color]),
+                }
+            }
+        }
+    }
+
+    fn check(dest: Rect, stride: usize, len: usize) {
+        let src = source(len);
+        for mode in [Mode::Overwrite, Mode::Blend] {
+            let background = Color::rgba(10, 20, 30, 200);
+            let mut expected = Image::from_color(W, H, background);
+            let mut actual = expected.clone();
+            reference(&mut expected, dest, &src, stride, mode);
+            blit(&mut actual, dest, &src, stride, mode);
+            let same = expected
+                .data()
+                .iter()
+                .zip(actual.data())
+                .all(|(a, b)| a.data == b.data);
+            assert!(
+                same,
+                "({}, {}) {}x{}",
+                dest.left(),
+                dest.top(),
+                dest.width(),
+                dest.height()
+            );
+        }
+    }
+
+    #[test]
+    fn every_edge() {
+        // From fully outside on one side, across every edge, to fully outside on the other,
+        // including negative positions
+        for y in -6..=8 {
+            for x in -7..=10 {
+                check(Rect::new(x, y, 5, 4), 5, 20);
+            }
+        }
+    }
+
+    #[test]
+    fn all_edges_clipped() {
+        for (x, y) in [(-2, -1), (-10, -10), (0, 0), (-1, 0)] {
+            check(Rect::new(x, y, 20, 15), 20, 300);
+        }
+    }
+
+    #[test]
+    fn wide_stride() {
+        // A region of a larger source, which must skip the rest of each row
+        for (x, y) in [(-3, -2), (2, 1), (5, 4), (-1, 3)] {
+            check(Rect::new(x, y, 5, 4), 13, 13 * 4);
+        }
+    }
+
+    #[test]
+    fn short_source() {
+        for (x, y) in [(-3, -2), (1, 1), (4, 3)] {
+            check(Rect::new(x, y, 5, 4), 5, 12);
+            check(Rect::new(x, y, 5, 4), 5, 0);
+        }
+    }
+
+    #[test]
+    fn empty_dest() {
+        check(Rect::new(2, 2, 0, 3), 0, 10);
+        check(Rect::new(2, 2, 3, 0), 3, 10);
+    }
+
+    #[test]
+    fn image_roi_draw() {
+        let src = Image::from_data(7, 5, source(35).into_boxed_slice()).unwrap();
+        let roi = src.roi(&Rect::new(2, 1, 4, 3));
+        for (x, y) in [(-3, -2), (0, 0), (5, 4), (6, -1)] {
+            let mut expected = Image::new(W, H);
+            let mut actual = expected.clone();
+            reference(
+                &mut expected,
+                Rect::new(x, y, 4, 3),
+                &src.data()[7 + 2..],
+                7,
+                Mode::Blend,
+            );
+            roi.draw(&mut actual, x, y);
+            assert!(expected
+                .data()
+                .iter()
+                .zip(actual.data())
+                .all(|(a, b)| a.data == b.data));
+        }
+    }
+}
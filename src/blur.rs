@@ -6,9 +6,10 @@ The struct MathColor is needed for the calculate with bigger numbers, the Color
 */
 
 #[cfg(not(feature = "std"))]
-use alloc::vec::Vec;
+use alloc::{vec, vec::Vec};
 
 use crate::color::Color;
+use crate::parallel;
 use core::ops::{Add, AddAssign, Sub};
 
 #[derive(Copy, Clone)]
@@ -19,6 +20,8 @@ struct MathColor {
 }
 
 impl MathColor {
+    const ZERO: MathColor = MathColor { r: 0, g: 0, b: 0 };
+
     fn new(color: Color) -> Self {
         MathColor {
             r: color.r() as isize,
@@ -74,6 +77,9 @@ impl Sub for MathColor {
 }
 
 pub(crate) fn gauss_blur(data: &mut [Color], w: u32, h: u32, r: f32) {
+    if w == 0 || h == 0 {
+        return;
+    }
     let bxs = boxes_for_gauss(r, 3);
     let mut tcl = data.to_owned();
 
@@ -127,92 +133,54 @@ fn box_blur(tcl: &mut [Color], scl: &mut [Color], w: usize, h: usize, r: usize)
     box_blur_h(tcl, scl, w, h, r);
 }
 
-#[inline(always)]
-fn box_blur_h(tcl: &mut [Color], scl: &mut [Color], w: usize, h: usize, r: usize) {
+/// Blur each row with a sliding sum, repeating the edge pixels
+fn box_blur_h(tcl: &[Color], scl: &mut [Color], w: usize, h: usize, r: usize) {
     let iarr: f32 = 1.0 / (r + r + 1) as f32;
-
-    for i in 0..h {
-        let mut ti: usize = i * w;
-        let mut li: usize = ti;
-        let mut ri: usize = ti + r;
-        let fv = MathColor::new(tcl[ti]);
-        let lv = MathColor::new(tcl[ti + w - 1]);
-
-        let mut val: MathColor = MathColor {
-            r: (r + 1) as isize * fv.r,
-            g: (r + 1) as isize * fv.g,
-            b: (r + 1) as isize * fv.b,
-        };
-
-        for j in 0..r {
-            val += MathColor::new(tcl[ti + j]);
-        }
-
-        for _ in 0..(r + 1) {
-            val += MathColor::new(tcl[ri]) - fv;
-            scl[ti] = val.get_multiplied_color(iarr);
-            ti += 1;
-            ri += 1;
-        }
-
-        for _ in (r + 1)..(w - r) {
-            val += MathColor::new(tcl[ri]) - MathColor::new(tcl[li]);
-            scl[ti] = val.get_multiplied_color(iarr);
-            ti += 1;
-            ri += 1;
-            li += 1;
-        }
-
-        for _ in (w - r)..w {
-            val += lv - MathColor::new(tcl[li]);
-            scl[ti] = val.get_multiplied_color(iarr);
-            ti += 1;
-            li += 1;
+    let r = r as isize;
+
+    parallel::for_each_band(&mut scl[..w * h], w, |y0, band| {
+        for (i, row) in band.chunks_mut(w).enumerate() {
+            let src = &tcl[(y0 + i) * w..(y0 + i + 1) * w];
+            let pixel = |x: isize| MathColor::new(src[x.clamp(0, w as isize - 1) as usize]);
+
+            let mut val = MathColor::ZERO;
+            for x in -r..=r {
+                val += pixel(x);
+            }
+            for (x, dst) in row.iter_mut().enumerate() {
+                let x = x as isize;
+                *dst = val.get_multiplied_color(iarr);
+                val += pixel(x + r + 1) - pixel(x - r);
+            }
         }
-    }
+    });
 }
 
-#[inline(always)]
-fn box_blur_t(tcl: &mut [Color], scl: &mut [Color], w: usize, h: usize, r: usize) {
+/// Blur each column with a sliding sum, repeating the edge pixels. Rows are processed in
+/// order, keeping the sums of every column, so that the buffer can be split into bands.
+fn box_blur_t(tcl: &[Color], scl: &mut [Color], w: usize, h: usize, r: usize) {
     let iarr: f32 = 1.0 / (r + r + 1) as f32;
+    let r = r as isize;
+    let src_row = |y: isize| {
+        let y = y.clamp(0, h as isize - 1) as usize;
+        &tcl[y * w..(y + 1) * w]
+    };
 
-    for i in 0..w {
-        let mut ti: usize = i;
-        let mut li: usize = ti;
-        let mut ri: usize = ti + r * w;
-        let fv = MathColor::new(tcl[ti]);
-        let lv = MathColor::new(tcl[ti + w * (h - 1)]);
-
-        let mut val: MathColor = MathColor {
-            r: (r + 1) as isize * fv.r,
-            g: (r + 1) as isize * fv.g,
-            b: (r + 1) as isize * fv.b,
-        };
-
-        for j in 0..r {
-            val += MathColor::new(tcl[ti + j * w]);
-        }
-
-        for _ in 0..(r + 1) {
-            val += MathColor::new(tcl[ri]) - fv;
-            scl[ti] = val.get_multiplied_color(iarr);
-            ti += w;
-            ri += w;
+    parallel::for_each_band(&mut scl[..w * h], w, |y0, band| {
+        let y0 = y0 as isize;
+        let mut vals = vec![MathColor::ZERO; w];
+        for y in y0 - r..=y0 + r {
+            for (val, &color) in vals.iter_mut().zip(src_row(y)) {
+                *val += MathColor::new(color);
+            }
         }
-
-        for _ in (r + 1)..(h - r) {
-            val += MathColor::new(tcl[ri]) - MathColor::new(tcl[li]);
-            scl[ti] = val.get_multiplied_color(iarr);
-            ti += w;
-            ri += w;
-            li += w;
+        for (i, row) in band.chunks_mut(w).enumerate() {
+            let y = y0 + i as isize;
+            let (next, last) = (src_row(y + r + 1), src_row(y - r));
+            for (x, dst) in row.iter_mut().enumerate() {
+                *dst = vals[x].get_multiplied_color(iarr);
+                vals[x] += MathColor::new(next[x]) - MathColor::new(last[x]);
+            }
         }
-
-        for _ in (h - r)..h {
-            val += lv - MathColor::new(tcl[li]);
-            scl[ti] = val.get_multiplied_color(iarr);
-            ti += w;
-            li += w;
-        }
-    }
+    });
 }
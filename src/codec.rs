@@ -0,0 +1,150 @@
+// SPDX-License-Identifier: MIT
+
+//! Small image decoders and encoders that only need `alloc`, so they work without `std`.
+//!
+//! For more formats, and for compressed BMP files, use the `image` feature.
+
+use core::fmt;
+
+use crate::image::Image;
+
+pub mod bmp;
+pub mod pnm;
+pub mod qoi;
+
+/// An error while decoding or encoding an image
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CodecError {
+    /// The data does not start with the signature of a supported format
+    UnknownFormat,
+    /// The data ended before the image was complete
+    UnexpectedEof,
+    /// The header contains invalid or inconsistent values
+    InvalidHeader,
+    /// The image uses a feature of the format that is not supported
+    Unsupported,
+    /// The image is too large to be stored in the format
+    TooLarge,
+}
+
+impl fmt::Display for CodecError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CodecError::UnknownFormat => write!(f, "unknown image format"),
+            CodecError::UnexpectedEof => write!(f, "unexpected end of image data"),
+            CodecError::InvalidHeader => write!(f, "invalid image header"),
+            CodecError::Unsupported => write!(f, "unsupported image format variant"),
+            CodecError::TooLarge => write!(f, "image too large for the format"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for CodecError {}
+
+/// Decode a QOI, BMP, PPM, PGM, PBM or PAM image, guessing the format from its signature
+pub fn decode(data: &[u8]) -> Result<Image, CodecError> {
+    if data.starts_with(qoi::MAGIC) {
+        qoi::decode(data)
+    } else if data.starts_with(bmp::MAGIC) {
+        bmp::decode(data)
+    } else if data.len() >= 2 && data[0] == b'P' && (b'1'..=b'7').contains(&data[1]) {
+        pnm::decode(data)
+    } else {
+        Err(CodecError::UnknownFormat)
+    }
+}
+
+/// Check that `w * h` pixels fit in memory and could be described by `len` bytes of data,
+/// where one byte can describe at most `pixels_per_byte` pixels.
+/// This stops corrupt headers from causing huge allocations.
+fn check_size(w: u32, h: u32, len: usize, pixels_per_byte: usize) -> Result<usize, CodecError> {
+    let pixels = (w as usize)
+        .checked_mul(h as usize)
+        .ok_or(CodecError::InvalidHeader)?;
+    if pixels > len.saturating_mul(pixels_per_byte) {
+        return Err(CodecError::UnexpectedEof);
+    }
+    Ok(pixels)
+}
+
+fn bytes<const N: usize>(data: &[u8], offset: usize) -> Result<[u8; N], CodecError> {
+    let mut out = [0; N];
+    out.copy_from_slice(
+        data.get(offset..offset + N)
+            .ok_or(CodecError::UnexpectedEof)?,
+    );
+    Ok(out)
+}
+
+fn u16_le(data: &[u8], offset: usize) -> Result<u16, CodecError> {
+    bytes(data, offset).map(u16::from_le_bytes)
+}
+
+fn u32_le(data: &[u8], offset: usize) -> Result<u32, CodecError> {
+    bytes(data, offset).map(u32::from_le_bytes)
+}
+
+fn u32_be(data: &[u8], offset: usize) -> Result<u32, CodecError> {
+    bytes(data, offset).map(u32::from_be_bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(not(feature = "std"))]
+    use alloc::vec::Vec;
+
+    use super::*;
+    use crate::color::Color;
+    use crate::renderer::Renderer;
+
+    /// An image with long runs, small and large color changes, repeated colors and
+    /// varying alpha, which between them use every kind of QOI chunk
+    pub(super) fn image() -> Image {
+        let (w, h) = (37, 9);
+        let mut state = 0x2545F491u32;
+        let mut random = move || {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state
+        };
+        let mut pixels = Vec::new();
+        for y in 0..h {
+            for x in 0..w {
+                let i = x as u8;
+                let color = match y {
+                    0..=2 => Color::rgb(10, 20, 30),
+                    3 => Color::rgb(i, i.wrapping_add(1), i.wrapping_sub(1)),
+                    4 => Color::rgb(i * 7, i * 6, i * 5),
+                    5 if x % 2 == 0 => Color::rgb(200, 100, 50),
+                    5 => Color::rgba(50, 100, 200, 128),
+                    6 => Color { data: random() },
+                    _ => Color {
+                        data: random() | 0xFF000000,
+                    },
+                };
+                pixels.push(color);
+            }
+        }
+        Image::from_data(w, h, pixels.into_boxed_slice()).unwrap()
+    }
+
+    /// Compare every channel, as `Color` equality ignores alpha
+    pub(super) fn assert_same(a: &Image, b: &Image) {
+        assert_eq!((a.width(), a.height()), (b.width(), b.height()));
+        for (i, (a, b)) in a.data().iter().zip(b.data()).enumerate() {
+            assert_eq!(a.data, b.data, "pixel {}", i);
+        }
+    }
+
+    #[test]
+    fn decode_any() {
+        let image = image();
+        assert_same(&decode(&qoi::encode(&image)).unwrap(), &image);
+        assert_same(&decode(&bmp::encode(&image).unwrap()).unwrap(), &image);
+        assert_same(&decode(&pnm::encode_pam(&image)).unwrap(), &image);
+        assert_eq!(decode(b"GIF89a").err(), Some(CodecError::UnknownFormat));
+        assert_eq!(decode(b"").err(), Some(CodecError::UnknownFormat));
+    }
+}
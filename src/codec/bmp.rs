@@ -0,0 +1,307 @@
+// SPDX-License-Identifier: MIT
+
+//! Uncompressed Windows bitmaps, with 1, 4, 8, 16, 24 or 32 bits per pixel
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+
+use super::{check_size, u16_le, u32_le, CodecError};
+use crate::color::Color;
+use crate::image::Image;
+use crate::renderer::Renderer;
+
+pub(super) const MAGIC: &[u8] = b"BM";
+
+const FILE_HEADER_LEN: usize = 14;
+const CORE_HEADER_LEN: u32 = 12;
+const INFO_HEADER_LEN: u32 = 40;
+const V4_HEADER_LEN: u32 = 108;
+
+const BI_RGB: u32 = 0;
+const BI_BITFIELDS: u32 = 3;
+const BI_ALPHABITFIELDS: u32 = 6;
+
+/// Extracts one channel from a pixel with a bit mask
+#[derive(Clone, Copy)]
+struct Channel {
+    mask: u32,
+    shift: u32,
+    max: u32,
+}
+
+impl Channel {
+    fn new(mask: u32) -> Self {
+        if mask == 0 {
+            return Channel {
+                mask,
+                shift: 0,
+                max: 0,
+            };
+        }
+        let shift = mask.trailing_zeros();
+        Channel {
+            mask,
+            shift,
+            max: mask >> shift,
+        }
+    }
+
+    fn get(&self, pixel: u32, default: u8) -> u8 {
+        // Masks may be up to 32 bits wide, so scale in 64 bits
+        (((pixel & self.mask) >> self.shift) as u64 * 255)
+            .checked_div(self.max as u64)
+            .map_or(default, |value| value as u8)
+    }
+}
+
+/// Decode an uncompressed BMP image
+pub fn decode(data: &[u8]) -> Result<Image, CodecError> {
+    if !data.starts_with(MAGIC) {
+        return Err(CodecError::UnknownFormat);
+    }
+    let pixel_offset = u32_le(data, 10)? as usize;
+    let header_len = u32_le(data, FILE_HEADER_LEN)?;
+
+    let (w, h, bpp, compression) = if header_len == CORE_HEADER_LEN {
+        let w = u16_le(data, 18)? as i32;
+        let h = u16_le(data, 20)? as i16 as i32;
+        (w, h, u16_le(data, 24)?, BI_RGB)
+    } else if header_len >= INFO_HEADER_LEN {
+        let w = u32_le(data, 18)? as i32;
+        let h = u32_le(data, 22)? as i32;
+        (w, h, u16_le(data, 28)?, u32_le(data, 30)?)
+    } else {
+        return Err(CodecError::InvalidHeader);
+    };
+    if w <= 0 || h == 0 {
+        return Err(CodecError::InvalidHeader);
+    }
+    let top_down = h < 0;
+    let (w, h) = (w as u32, h.unsigned_abs());
+
+    let masks_offset = FILE_HEADER_LEN + INFO_HEADER_LEN as usize;
+    let (red, green, blue, alpha) = match (compression, bpp) {
+        (BI_RGB, 16) => (0x7C00, 0x03E0, 0x001F, 0),
+        (BI_RGB, 24) | (BI_RGB, 32) => (0x00FF0000, 0x0000FF00, 0x000000FF, 0),
+        (BI_RGB, 1) | (BI_RGB, 4) | (BI_RGB, 8) => (0, 0, 0, 0),
+        (BI_BITFIELDS, 16)
+        | (BI_BITFIELDS, 32)
+        | (BI_ALPHABITFIELDS, 16)
+        | (BI_ALPHABITFIELDS, 32) => {
+            let alpha = if compression == BI_ALPHABITFIELDS || header_len >= 56 {
+                u32_le(data, masks_offset + 12)?
+            } else {
+                0
+            };
+            (
+                u32_le(data, masks_offset)?,
+                u32_le(data, masks_offset + 4)?,
+                u32_le(data, masks_offset + 8)?,
+                alpha,
+            )
+        }
+        _ => return Err(CodecError::Unsupported),
+    };
+    let channels = [
+        Channel::new(red),
+        Channel::new(green),
+        Channel::new(blue),
+        Channel::new(alpha),
+    ];
+
+    let mut palette = Vec::new();
+    if bpp <= 8 {
+        let entry_len = if header_len == CORE_HEADER_LEN { 3 } else { 4 };
+        let used = if header_len >= INFO_HEADER_LEN {
+            u32_le(data, 46)? as usize
+        } else {
+            0
+        };
+        let count = if used == 0 || used > 1 << bpp {
+            1 << bpp
+        } else {
+            used
+        };
+        let start = FILE_HEADER_LEN + header_len as usize;
+        for i in 0..count {
+            let entry = data
+                .get(start + i * entry_len..start + i * entry_len + 3)
+                .ok_or(CodecError::UnexpectedEof)?;
+            palette.push(Color::rgb(entry[2], entry[1], entry[0]));
+        }
+    }
+
+    let row_len = (w as usize * bpp as usize).div_ceil(32) * 4;
+    let pixel_data = data.get(pixel_offset..).ok_or(CodecError::UnexpectedEof)?;
+    if row_len
+        .checked_mul(h as usize)
+        .is_none_or(|len| pixel_data.len() < len)
+    {
+        return Err(CodecError::UnexpectedEof);
+    }
+    let len = check_size(w, h, pixel_data.len(), 8)?;
+
+    let mut pixels = Vec::with_capacity(len);
+    let mut has_alpha = false;
+    for y in 0..h as usize {
+        let src_y = if top_down { y } else { h as usize - 1 - y };
+        let row = &pixel_data[src_y * row_len..(src_y + 1) * row_len];
+        for x in 0..w as usize {
+            let color = match bpp {
+                1 | 4 | 8 => {
+                    let bit = x * bpp as usize;
+                    let byte = row[bit / 8] as u32;
+                    let index = (byte >> (8 - bpp as usize - bit % 8)) & ((1 << bpp) - 1);
+                    *palette
+                        .get(index as usize)
+                        .ok_or(CodecError::InvalidHeader)?
+                }
+                _ => {
+                    let bytes = bpp as usize / 8;
+                    let pixel = row[x * bytes..x * bytes + bytes]
+                        .iter()
+                        .rev()
+                        .fold(0, |pixel, &b| pixel << 8 | b as u32);
+                    let a = channels[3].get(pixel, 255);
+                    has_alpha |= alpha != 0 && a != 0;
+                    Color::rgba(
+                        channels[0].get(pixel, 0),
+                        channels[1].get(pixel, 0),
+                        channels[2].get(pixel, 0),
+                        a,
+                    )
+                }
+            };
+            pixels.push(color);
+        }
+    }
+
+    // Files with an alpha mask but only zero alpha values are meant to be opaque
+    if alpha != 0 && !has_alpha {
+        for pixel in pixels.iter_mut() {
+            pixel.data |= 0xFF000000;
+        }
+    }
+
+    Image::from_data(w, h, pixels.into_boxed_slice()).ok_or(CodecError::InvalidHeader)
+}
+
+/// Length of the pixel data and of the whole file of a `w` by `h` image encoded by [`encode`],
+/// which must fit the 32 bit size fields
+fn encoded_len(w: u32, h: u32) -> Result<(u32, u32), CodecError> {
+    let pixel_offset = FILE_HEADER_LEN as u32 + V4_HEADER_LEN;
+    (w as u64)
+        .checked_mul(h as u64)
+        .and_then(|pixels| pixels.checked_mul(4))
+        .and_then(|len| u32::try_from(len).ok())
+        .and_then(|image_len| Some((image_len, pixel_offset.checked_add(image_len)?)))
+        .ok_or(CodecError::TooLarge)
+}
+
+/// Encode an image as a 32 bit BMP with an alpha channel.
+/// Returns [`CodecError::TooLarge`] if the file would be 4 GiB or larger.
+pub fn encode(image: &Image) -> Result<Vec<u8>, CodecError> {
+    let (w, h) = (image.width(), image.height());
+    let pixel_offset = FILE_HEADER_LEN as u32 + V4_HEADER_LEN;
+    let (image_len, file_len) = encoded_len(w, h)?;
+    // Each side is at most 2^30 pixels, so both fit an i32
+    let (width, height) = (w as i32, h as i32);
+
+    let mut out = Vec::with_capacity(file_len as usize);
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&file_len.to_le_bytes());
+    out.extend_from_slice(&[0; 4]);
+    out.extend_from_slice(&pixel_offset.to_le_bytes());
+
+    out.extend_from_slice(&V4_HEADER_LEN.to_le_bytes());
+    out.extend_from_slice(&width.to_le_bytes());
+    // Negative height stores the rows top to bottom
+    out.extend_from_slice(&(-height).to_le_bytes());
+    out.extend_from_slice(&1u16.to_le_bytes());
+    out.extend_from_slice(&32u16.to_le_bytes());
+    out.extend_from_slice(&BI_BITFIELDS.to_le_bytes());
+    out.extend_from_slice(&image_len.to_le_bytes());
+    // 72 DPI in pixels per meter
+    out.extend_from_slice(&2835u32.to_le_bytes());
+    out.extend_from_slice(&2835u32.to_le_bytes());
+    out.extend_from_slice(&[0; 8]);
+    for mask in [0x00FF0000u32, 0x0000FF00, 0x000000FF, 0xFF000000] {
+        out.extend_from_slice(&mask.to_le_bytes());
+    }
+    // sRGB color space, followed by unused endpoints and gamma
+    out.extend_from_slice(b"BGRs");
+    out.extend_from_slice(&[0; 48]);
+
+    for pixel in image.data() {
+        out.extend_from_slice(&pixel.data.to_le_bytes());
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{assert_same, image};
+    use super::*;
+
+    /// A one pixel, 32 bit BMP with the given color masks
+    fn bitfields(pixel: u32, masks: [u32; 3]) -> Vec<u8> {
+        let pixel_offset = FILE_HEADER_LEN as u32 + INFO_HEADER_LEN + 12;
+        let mut out = Vec::new();
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&(pixel_offset + 4).to_le_bytes());
+        out.extend_from_slice(&[0; 4]);
+        out.extend_from_slice(&pixel_offset.to_le_bytes());
+        out.extend_from_slice(&INFO_HEADER_LEN.to_le_bytes());
+        out.extend_from_slice(&1i32.to_le_bytes());
+        out.extend_from_slice(&1i32.to_le_bytes());
+        out.extend_from_slice(&1u16.to_le_bytes());
+        out.extend_from_slice(&32u16.to_le_bytes());
+        out.extend_from_slice(&BI_BITFIELDS.to_le_bytes());
+        out.extend_from_slice(&[0; 20]);
+        for mask in masks {
+            out.extend_from_slice(&mask.to_le_bytes());
+        }
+        out.extend_from_slice(&pixel.to_le_bytes());
+        out
+    }
+
+    #[test]
+    fn full_width_mask() {
+        let masks = [0xFFFFFFFF, 0, 0];
+        let image = decode(&bitfields(0xFFFFFFFF, masks)).unwrap();
+        assert_eq!(image.data()[0].r(), 255);
+        let image = decode(&bitfields(0x80000000, masks)).unwrap();
+        assert_eq!(image.data()[0].r(), 127);
+        let image = decode(&bitfields(0, masks)).unwrap();
+        assert_eq!(image.data()[0].r(), 0);
+    }
+
+    #[test]
+    fn round_trip() {
+        let image = image();
+        assert_same(&decode(&encode(&image).unwrap()).unwrap(), &image);
+    }
+
+    #[test]
+    fn truncated() {
+        let data = encode(&image()).unwrap();
+        for len in 0..data.len() {
+            assert!(decode(&data[..len]).is_err(), "{}", len);
+        }
+    }
+
+    #[test]
+    fn encoded_len_limit() {
+        let pixel_offset = FILE_HEADER_LEN as u32 + V4_HEADER_LEN;
+        assert_eq!(encoded_len(3, 2), Ok((24, pixel_offset + 24)));
+        assert_eq!(
+            encoded_len(65535, 16383),
+            Ok((65535 * 16383 * 4, pixel_offset + 65535 * 16383 * 4))
+        );
+        assert_eq!(encoded_len(32768, 32768), Err(CodecError::TooLarge));
+        assert_eq!(encoded_len(65536, 16384), Err(CodecError::TooLarge));
+        assert_eq!(encoded_len(u32::MAX, u32::MAX), Err(CodecError::TooLarge));
+        assert_eq!(encoded_len(1, u32::MAX / 4), Err(CodecError::TooLarge));
+    }
+}
@@ -0,0 +1,309 @@
+// SPDX-License-Identifier: MIT
+
+//! Netpbm images: PBM, PGM and PPM in ASCII or binary form, and PAM
+
+#[cfg(not(feature = "std"))]
+use alloc::{format, vec::Vec};
+use core::str;
+
+use super::{check_size, CodecError};
+use crate::color::Color;
+use crate::image::Image;
+use crate::renderer::Renderer;
+
+/// Reads the whitespace separated header fields, skipping comments
+struct Header<'a> {
+    data: &'a [u8],
+    i: usize,
+}
+
+impl<'a> Header<'a> {
+    fn skip_space(&mut self) {
+        while let Some(&b) = self.data.get(self.i) {
+            if b == b'#' {
+                while self.data.get(self.i).is_some_and(|&b| b != b'\n') {
+                    self.i += 1;
+                }
+            } else if b.is_ascii_whitespace() {
+                self.i += 1;
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn token(&mut self) -> Result<&'a [u8], CodecError> {
+        self.skip_space();
+        let start = self.i;
+        while self
+            .data
+            .get(self.i)
+            .is_some_and(|b| !b.is_ascii_whitespace())
+        {
+            self.i += 1;
+        }
+        if start == self.i {
+            return Err(CodecError::UnexpectedEof);
+        }
+        Ok(&self.data[start..self.i])
+    }
+
+    fn number(&mut self) -> Result<u32, CodecError> {
+        str::from_utf8(self.token()?)
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .ok_or(CodecError::InvalidHeader)
+    }
+
+    /// Read a single bit of an ASCII PBM, where digits do not need to be separated
+    fn bit(&mut self) -> Result<u32, CodecError> {
+        self.skip_space();
+        let b = *self.data.get(self.i).ok_or(CodecError::UnexpectedEof)?;
+        self.i += 1;
+        match b {
+            b'0' => Ok(0),
+            b'1' => Ok(1),
+            _ => Err(CodecError::InvalidHeader),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Tuple {
+    BlackAndWhite,
+    Gray,
+    GrayAlpha,
+    Rgb,
+    RgbAlpha,
+}
+
+impl Tuple {
+    fn channels(self) -> usize {
+        match self {
+            Tuple::BlackAndWhite | Tuple::Gray => 1,
+            Tuple::GrayAlpha => 2,
+            Tuple::Rgb => 3,
+            Tuple::RgbAlpha => 4,
+        }
+    }
+
+    fn color(self, s: &[u8]) -> Color {
+        match self {
+            // In PBM files 1 is black, this is handled before getting here
+            Tuple::BlackAndWhite | Tuple::Gray => Color::rgb(s[0], s[0], s[0]),
+            Tuple::GrayAlpha => Color::rgba(s[0], s[0], s[0], s[1]),
+            Tuple::Rgb => Color::rgb(s[0], s[1], s[2]),
+            Tuple::RgbAlpha => Color::rgba(s[0], s[1], s[2], s[3]),
+        }
+    }
+}
+
+/// Decode a PBM, PGM, PPM or PAM image
+pub fn decode(data: &[u8]) -> Result<Image, CodecError> {
+    if data.len() < 2 || data[0] != b'P' {
+        return Err(CodecError::UnknownFormat);
+    }
+    let kind = data[1];
+    let mut header = Header { data, i: 2 };
+
+    let (w, h, maxval, tuple) = match kind {
+        b'1' | b'4' => (header.number()?, header.number()?, 1, Tuple::BlackAndWhite),
+        b'2' | b'5' => (
+            header.number()?,
+            header.number()?,
+            header.number()?,
+            Tuple::Gray,
+        ),
+        b'3' | b'6' => (
+            header.number()?,
+            header.number()?,
+            header.number()?,
+            Tuple::Rgb,
+        ),
+        b'7' => {
+            let (mut w, mut h, mut depth, mut maxval, mut tuple) = (0, 0, 0, 0, None);
+            loop {
+                match header.token()? {
+                    b"WIDTH" => w = header.number()?,
+                    b"HEIGHT" => h = header.number()?,
+                    b"DEPTH" => depth = header.number()?,
+                    b"MAXVAL" => maxval = header.number()?,
+                    b"TUPLTYPE" => {
+                        tuple = Some(match header.token()? {
+                            b"BLACKANDWHITE" => Tuple::BlackAndWhite,
+                            b"GRAYSCALE" => Tuple::Gray,
+                            b"GRAYSCALE_ALPHA" => Tuple::GrayAlpha,
+                            b"RGB" => Tuple::Rgb,
+                            b"RGB_ALPHA" => Tuple::RgbAlpha,
+                            _ => return Err(CodecError::Unsupported),
+                        })
+                    }
+                    b"ENDHDR" => break,
+                    _ => return Err(CodecError::InvalidHeader),
+                }
+            }
+            let tuple = match tuple {
+                Some(tuple) => tuple,
+                None => match depth {
+                    1 => Tuple::Gray,
+                    2 => Tuple::GrayAlpha,
+                    3 => Tuple::Rgb,
+                    4 => Tuple::RgbAlpha,
+                    _ => return Err(CodecError::InvalidHeader),
+                },
+            };
+            if depth as usize != tuple.channels() {
+                return Err(CodecError::InvalidHeader);
+            }
+            (w, h, maxval, tuple)
+        }
+        _ => return Err(CodecError::UnknownFormat),
+    };
+    if w == 0 || h == 0 || maxval == 0 || maxval > 0xFFFF {
+        return Err(CodecError::InvalidHeader);
+    }
+    // A single whitespace character separates the header from binary data
+    header.i += 1;
+
+    let channels = tuple.channels();
+    let binary = matches!(kind, b'4' | b'5' | b'6' | b'7');
+    let sample_len = if maxval > 0xFF { 2 } else { 1 };
+    let len = check_size(w, h, data.len(), if kind == b'4' { 8 } else { 1 })?;
+
+    let mut pixels = Vec::with_capacity(len);
+    let mut samples = [0u8; 4];
+    for y in 0..h as usize {
+        for x in 0..w as usize {
+            if kind == b'1' || kind == b'4' {
+                let bit = if kind == b'1' {
+                    header.bit()?
+                } else {
+                    let row_len = (w as usize).div_ceil(8);
+                    let byte = *data
+                        .get(header.i + y * row_len + x / 8)
+                        .ok_or(CodecError::UnexpectedEof)?;
+                    ((byte >> (7 - x % 8)) & 1) as u32
+                };
+                pixels.push(if bit == 1 {
+                    Color::rgb(0, 0, 0)
+                } else {
+                    Color::rgb(255, 255, 255)
+                });
+                continue;
+            }
+
+            for sample in samples.iter_mut().take(channels) {
+                let value = if binary {
+                    let offset = header.i;
+                    let bytes = data
+                        .get(offset..offset + sample_len)
+                        .ok_or(CodecError::UnexpectedEof)?;
+                    header.i += sample_len;
+                    bytes.iter().fold(0, |value, &b| value << 8 | b as u32)
+                } else {
+                    header.number()?
+                };
+                if value > maxval {
+                    return Err(CodecError::InvalidHeader);
+                }
+                *sample = ((value * 255 + maxval / 2) / maxval) as u8;
+            }
+            // PAM black and white samples are 1 for white, unlike PBM
+            if tuple == Tuple::BlackAndWhite {
+                samples[0] = if samples[0] > 0 { 255 } else { 0 };
+            }
+            pixels.push(tuple.color(&samples));
+        }
+    }
+
+    Image::from_data(w, h, pixels.into_boxed_slice()).ok_or(CodecError::InvalidHeader)
+}
+
+/// Encode an image as a binary PPM, dropping the alpha channel
+pub fn encode_ppm(image: &Image) -> Vec<u8> {
+    let mut out = Vec::with_capacity(image.data().len() * 3 + 32);
+    out.extend_from_slice(format!("P6\n{} {}\n255\n", image.width(), image.height()).as_bytes());
+    for pixel in image.data() {
+        out.extend_from_slice(&[pixel.r(), pixel.g(), pixel.b()]);
+    }
+    out
+}
+
+/// Encode an image as a PAM with an alpha channel
+pub fn encode_pam(image: &Image) -> Vec<u8> {
+    let mut out = Vec::with_capacity(image.data().len() * 4 + 80);
+    out.extend_from_slice(
+        format!(
+            "P7\nWIDTH {}\nHEIGHT {}\nDEPTH 4\nMAXVAL 255\nTUPLTYPE RGB_ALPHA\nENDHDR\n",
+            image.width(),
+            image.height()
+        )
+        .as_bytes(),
+    );
+    for pixel in image.data() {
+        out.extend_from_slice(&[pixel.r(), pixel.g(), pixel.b(), pixel.a()]);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{assert_same, image};
+    use super::*;
+
+    #[test]
+    fn round_trip_pam() {
+        let image = image();
+        assert_same(&decode(&encode_pam(&image)).unwrap(), &image);
+    }
+
+    #[test]
+    fn round_trip_ppm() {
+        let image = image();
+        let decoded = decode(&encode_ppm(&image)).unwrap();
+        assert_eq!(decoded.width(), image.width());
+        assert_eq!(decoded.height(), image.height());
+        for (a, b) in decoded.data().iter().zip(image.data()) {
+            assert_eq!(a.data, b.data | 0xFF000000);
+        }
+    }
+
+    #[test]
+    fn ascii() {
+        let image = decode(b"P1\n# comment\n3 2\n101\n0 1 0\n").unwrap();
+        let black = Color::rgb(0, 0, 0).data;
+        let white = Color::rgb(255, 255, 255).data;
+        let pixels: Vec<u32> = image.data().iter().map(|c| c.data).collect();
+        assert_eq!(pixels, [black, white, black, white, black, white]);
+
+        let image = decode(b"P2 2 1 4 0 4").unwrap();
+        assert_eq!(image.data()[0].data, black);
+        assert_eq!(image.data()[1].data, white);
+
+        let image = decode(b"P3 1 1 65535 65535 32768 0").unwrap();
+        assert_eq!(image.data()[0].data, Color::rgb(255, 128, 0).data);
+    }
+
+    #[test]
+    fn truncated() {
+        let image = image();
+        for data in [encode_ppm(&image), encode_pam(&image)] {
+            for len in 0..data.len() {
+                assert!(decode(&data[..len]).is_err(), "{}", len);
+            }
+        }
+        assert!(decode(b"P3 1 1 255 1 2").is_err());
+    }
+
+    #[test]
+    fn invalid_header() {
+        assert_eq!(decode(b"P5 0 1 255 ").err(), Some(CodecError::InvalidHeader));
+        assert_eq!(decode(b"P2 1 1 255 256").err(), Some(CodecError::InvalidHeader));
+        assert_eq!(decode(b"P6 1 x 255 ").err(), Some(CodecError::InvalidHeader));
+        assert_eq!(
+            decode(b"P7\nWIDTH 1\nHEIGHT 1\nDEPTH 3\nTUPLTYPE RGB_ALPHA\nMAXVAL 255\nENDHDR\n")
+                .err(),
+            Some(CodecError::InvalidHeader)
+        );
+    }
+}
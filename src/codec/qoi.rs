@@ -0,0 +1,199 @@
+// SPDX-License-Identifier: MIT
+
+//! The Quite OK Image format, see <https://qoiformat.org/qoi-specification.pdf>
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+use super::{check_size, u32_be, CodecError};
+use crate::color::Color;
+use crate::image::Image;
+use crate::renderer::Renderer;
+
+pub(super) const MAGIC: &[u8] = b"qoif";
+
+const HEADER_LEN: usize = 14;
+const END_MARKER: [u8; 8] = [0, 0, 0, 0, 0, 0, 0, 1];
+
+const OP_INDEX: u8 = 0x00;
+const OP_DIFF: u8 = 0x40;
+const OP_LUMA: u8 = 0x80;
+const OP_RUN: u8 = 0xC0;
+const OP_RGB: u8 = 0xFE;
+const OP_RGBA: u8 = 0xFF;
+const OP_MASK: u8 = 0xC0;
+
+/// Longest run a single byte can encode
+const MAX_RUN: u8 = 62;
+
+fn hash(c: Color) -> usize {
+    (c.r() as usize * 3 + c.g() as usize * 5 + c.b() as usize * 7 + c.a() as usize * 11) % 64
+}
+
+/// Decode a QOI image
+pub fn decode(data: &[u8]) -> Result<Image, CodecError> {
+    if !data.starts_with(MAGIC) {
+        return Err(CodecError::UnknownFormat);
+    }
+    let w = u32_be(data, 4)?;
+    let h = u32_be(data, 8)?;
+    let channels = *data.get(12).ok_or(CodecError::UnexpectedEof)?;
+    if w == 0 || h == 0 || !(3..=4).contains(&channels) {
+        return Err(CodecError::InvalidHeader);
+    }
+    let len = check_size(w, h, data.len(), MAX_RUN as usize)?;
+
+    let mut index = [Color::rgba(0, 0, 0, 0); 64];
+    let mut px = Color::rgba(0, 0, 0, 255);
+    let mut pixels = Vec::with_capacity(len);
+    let mut i = HEADER_LEN;
+    let mut byte = || {
+        let b = *data.get(i).ok_or(CodecError::UnexpectedEof)?;
+        i += 1;
+        Ok(b)
+    };
+
+    while pixels.len() < len {
+        let b1 = byte()?;
+        match b1 {
+            OP_RGB => {
+                px = Color::rgba(byte()?, byte()?, byte()?, px.a());
+            }
+            OP_RGBA => {
+                px = Color::rgba(byte()?, byte()?, byte()?, byte()?);
+            }
+            _ => match b1 & OP_MASK {
+                OP_INDEX => px = index[b1 as usize],
+                OP_DIFF => {
+                    let dr = ((b1 >> 4) & 0x03).wrapping_sub(2);
+                    let dg = ((b1 >> 2) & 0x03).wrapping_sub(2);
+                    let db = (b1 & 0x03).wrapping_sub(2);
+                    px = Color::rgba(
+                        px.r().wrapping_add(dr),
+                        px.g().wrapping_add(dg),
+                        px.b().wrapping_add(db),
+                        px.a(),
+                    );
+                }
+                OP_LUMA => {
+                    let b2 = byte()?;
+                    let dg = (b1 & 0x3F).wrapping_sub(32);
+                    let dr = dg.wrapping_add(b2 >> 4).wrapping_sub(8);
+                    let db = dg.wrapping_add(b2 & 0x0F).wrapping_sub(8);
+                    px = Color::rgba(
+                        px.r().wrapping_add(dr),
+                        px.g().wrapping_add(dg),
+                        px.b().wrapping_add(db),
+                        px.a(),
+                    );
+                }
+                _ => {
+                    let run = (b1 & 0x3F) as usize + 1;
+                    let run = run.min(len - pixels.len());
+                    pixels.extend(core::iter::repeat_n(px, run));
+                    continue;
+                }
+            },
+        }
+        index[hash(px)] = px;
+        pixels.push(px);
+    }
+
+    Image::from_data(w, h, pixels.into_boxed_slice()).ok_or(CodecError::InvalidHeader)
+}
+
+/// Encode an image as QOI with an alpha channel
+pub fn encode(image: &Image) -> Vec<u8> {
+    let pixels = image.data();
+    let mut out = Vec::with_capacity(HEADER_LEN + pixels.len() + END_MARKER.len());
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&image.width().to_be_bytes());
+    out.extend_from_slice(&image.height().to_be_bytes());
+    // Four channels, sRGB with linear alpha
+    out.extend_from_slice(&[4, 0]);
+
+    let mut index = [Color::rgba(0, 0, 0, 0); 64];
+    let mut prev = Color::rgba(0, 0, 0, 255);
+    let mut run = 0;
+    for (i, &px) in pixels.iter().enumerate() {
+        if px.data == prev.data {
+            run += 1;
+            if run == MAX_RUN || i + 1 == pixels.len() {
+                out.push(OP_RUN | (run - 1));
+                run = 0;
+            }
+            continue;
+        }
+        if run > 0 {
+            out.push(OP_RUN | (run - 1));
+            run = 0;
+        }
+
+        let hash = hash(px);
+        if index[hash].data == px.data {
+            out.push(OP_INDEX | hash as u8);
+        } else {
+            index[hash] = px;
+            if px.a() == prev.a() {
+                let dr = px.r().wrapping_sub(prev.r()) as i8;
+                let dg = px.g().wrapping_sub(prev.g()) as i8;
+                let db = px.b().wrapping_sub(prev.b()) as i8;
+                let dr_dg = dr.wrapping_sub(dg);
+                let db_dg = db.wrapping_sub(dg);
+                if (-2..2).contains(&dr) && (-2..2).contains(&dg) && (-2..2).contains(&db) {
+                    out.push(
+                        OP_DIFF | ((dr + 2) as u8) << 4 | ((dg + 2) as u8) << 2 | (db + 2) as u8,
+                    );
+                } else if (-32..32).contains(&dg)
+                    && (-8..8).contains(&dr_dg)
+                    && (-8..8).contains(&db_dg)
+                {
+                    out.push(OP_LUMA | (dg + 32) as u8);
+                    out.push(((dr_dg + 8) as u8) << 4 | (db_dg + 8) as u8);
+                } else {
+                    out.extend_from_slice(&[OP_RGB, px.r(), px.g(), px.b()]);
+                }
+            } else {
+                out.extend_from_slice(&[OP_RGBA, px.r(), px.g(), px.b(), px.a()]);
+            }
+        }
+        prev = px;
+    }
+
+    out.extend_from_slice(&END_MARKER);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{assert_same, image};
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let image = image();
+        let data = encode(&image);
+        // Runs, index, diff and luma chunks make it smaller than RGBA
+        assert!(data.len() < image.data().len() * 4);
+        assert_same(&decode(&data).unwrap(), &image);
+    }
+
+    #[test]
+    fn truncated() {
+        let data = encode(&image());
+        // The end marker is not needed to decode the pixels
+        for len in 0..data.len() - END_MARKER.len() {
+            assert!(decode(&data[..len]).is_err(), "{}", len);
+        }
+    }
+
+    #[test]
+    fn invalid_header() {
+        let mut data = encode(&image());
+        data[12] = 5;
+        assert_eq!(decode(&data).err(), Some(CodecError::InvalidHeader));
+        data[12] = 4;
+        data[4..12].copy_from_slice(&[0xFF; 8]);
+        assert!(decode(&data).is_err());
+    }
+}
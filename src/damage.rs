@@ -0,0 +1,747 @@
+// SPDX-License-Identifier: MIT
+
+//! Recording which parts of a renderer changed, so that only those are sent to the screen.
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+use core::cell::Cell;
+use core::cmp;
+
+use crate::color::Color;
+use crate::flood::{self, Connectivity};
+use crate::graphicspath::GraphicsPath;
+use crate::image::ImageRoi;
+use crate::math;
+use crate::pattern::Pattern;
+use crate::rect::Rect;
+use crate::renderer::Renderer;
+use crate::resample::Filter;
+use crate::subpixel::Path;
+#[cfg(any(feature = "unifont", feature = "unifont-subset"))]
+use crate::text::{self, Font, TextStyle, Unifont};
+use crate::transform::Affine;
+use crate::triangle::{Mapping, Vertex};
+use crate::Mode;
+
+/// Rects kept before the closest ones are merged
+const MAX_RECTS: usize = 16;
+
+/// The cost of updating one more rect, in pixels. Two rects are merged when their union
+/// adds fewer pixels than this.
+const RECT_COST: usize = 32 * 32;
+
+/// Above this fraction of the renderer, flushing syncs everything at once
+const SYNC_FRACTION: f32 = 0.5;
+
+/// A set of changed rects, merged as they are added
+#[derive(Clone, Debug, Default)]
+pub struct Damage {
+    rects: Vec<Rect>,
+    all: bool,
+}
+
+impl Damage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a changed rect, merging it with the rects it is cheaper to update together
+    pub fn add(&mut self, rect: Rect) {
+        if self.all || rect.is_empty() {
+            return;
+        }
+        let mut rect = rect;
+        while let Some(i) = self
+            .rects
+            .iter()
+            .position(|other| extra_area(other, &rect) <= RECT_COST)
+        {
+            rect = rect.container(&self.rects.swap_remove(i));
+        }
+        self.rects.push(rect);
+
+        if self.rects.len() > MAX_RECTS {
+            let mut best = (0, 1, usize::MAX);
+            for i in 0..self.rects.len() {
+                for j in i + 1..self.rects.len() {
+                    let extra = extra_area(&self.rects[i], &self.rects[j]);
+                    if extra < best.2 {
+                        best = (i, j, extra);
+                    }
+                }
+            }
+            let other = self.rects.swap_remove(best.1);
+            self.rects[best.0] = self.rects[best.0].container(&other);
+        }
+    }
+
+    /// Record that everything changed
+    pub fn add_all(&mut self) {
+        self.all = true;
+        self.rects.clear();
+    }
+
+    /// Whether everything changed, in which case `rects` is empty
+    pub fn is_all(&self) -> bool {
+        self.all
+    }
+
+    pub fn is_empty(&self) -> bool {
+        !self.all && self.rects.is_empty()
+    }
+
+    /// The changed rects
+    pub fn rects(&self) -> &[Rect] {
+        &self.rects
+    }
+
+    pub fn clear(&mut self) {
+        self.all = false;
+        self.rects.clear();
+    }
+}
+
+/// Pixels covered by the union of two rects and by neither of them
+fn extra_area(a: &Rect, b: &Rect) -> usize {
+    (a.container(b).area() + a.intersection(b).area()).saturating_sub(a.area() + b.area())
+}
+
+/// A renderer that records the bounds of everything drawn on it, and on `sync` or `update`
+/// sends only the changed rects to the screen with `update_rects`.
+///
+/// Drawing on the inner renderer, or through `data_mut`, is not tracked: use
+/// [`DamageTracker::damage_mut`] to record it, or everything is assumed changed after
+/// `data_mut`.
+pub struct DamageTracker<R: Renderer> {
+    renderer: R,
+    damage: Damage,
+}
+
+impl<R: Renderer> DamageTracker<R> {
+    /// Track drawing on a renderer, starting with everything changed
+    pub fn new(renderer: R) -> Self {
+        let mut damage = Damage::new();
+        damage.add_all();
+        DamageTracker { renderer, damage }
+    }
+
+    pub fn inner(&self) -> &R {
+        &self.renderer
+    }
+
+    /// The tracked renderer, to handle events or draw without tracking
+    pub fn inner_mut(&mut self) -> &mut R {
+        &mut self.renderer
+    }
+
+    pub fn into_inner(self) -> R {
+        self.renderer
+    }
+
+    pub fn damage(&self) -> &Damage {
+        &self.damage
+    }
+
+    pub fn damage_mut(&mut self) -> &mut Damage {
+        &mut self.damage
+    }
+
+    /// Send the changed rects to the screen, syncing everything if they cover most of it
+    pub fn flush(&mut self) -> bool {
+        if self.damage.is_empty() {
+            return true;
+        }
+        let area: usize = self.damage.rects().iter().map(Rect::area).sum();
+        let total = self.renderer.width() as usize * self.renderer.height() as usize;
+        let result = if self.damage.is_all() || area as f32 > total as f32 * SYNC_FRACTION {
+            self.renderer.sync()
+        } else {
+            let rects: Vec<_> = self
+                .damage
+                .rects()
+                .iter()
+                .map(|rect| (rect.left(), rect.top(), rect.width(), rect.height()))
+                .collect();
+            self.renderer.update_rects(&rects)
+        };
+        self.damage.clear();
+        result
+    }
+
+    /// Record a changed rect, clipped to the renderer
+    fn add(&mut self, x: i32, y: i32, w: u32, h: u32) {
+        let bounds = Rect::new(0, 0, self.renderer.width(), self.renderer.height());
+        self.damage.add(Rect::new(x, y, w, h).intersection(&bounds));
+    }
+
+    /// Record the rect between two corners, both included
+    fn add_corners(&mut self, x0: i32, y0: i32, x1: i32, y1: i32) {
+        let (left, right) = (cmp::min(x0, x1), cmp::max(x0, x1));
+        let (top, bottom) = (cmp::min(y0, y1), cmp::max(y0, y1));
+        self.add(
+            left,
+            top,
+            (right - left) as u32 + 1,
+            (bottom - top) as u32 + 1,
+        );
+    }
+
+    /// Record the rect covering a circle
+    fn add_circle(&mut self, x0: i32, y0: i32, radius: i32) {
+        let radius = radius.abs();
+        self.add_corners(x0 - radius, y0 - radius, x0 + radius, y0 + radius);
+    }
+
+    /// Record the pixels touched by fractional points grown by `margin` on every side
+    fn add_points(&mut self, points: impl IntoIterator<Item = [f32; 2]>, margin: f32) {
+        let mut min = [f32::MAX; 2];
+        let mut max = [f32::MIN; 2];
+        for point in points {
+            for c in 0..2 {
+                min[c] = min[c].min(point[c]);
+                max[c] = max[c].max(point[c]);
+            }
+        }
+        if min[0] > max[0] || min[1] > max[1] {
+            return;
+        }
+        let (left, top) = (math::floor(min[0] - margin), math::floor(min[1] - margin));
+        let (right, bottom) = (math::ceil(max[0] + margin), math::ceil(max[1] + margin));
+        self.add(
+            left,
+            top,
+            right.saturating_sub(left) as u32,
+            bottom.saturating_sub(top) as u32,
+        );
+    }
+
+    /// Record the rect covering the points of a path
+    fn add_graphics_path(&mut self, graphicspath: &GraphicsPath) {
+        let points = graphicspath.points.iter();
+        self.add_points(points.map(|&(x, y, _)| [x as f32, y as f32]), 0.0);
+    }
+}
+
+impl<R: Renderer> Renderer for DamageTracker<R> {
+    fn width(&self) -> u32 {
+        self.renderer.width()
+    }
+
+    fn height(&self) -> u32 {
+        self.renderer.height()
+    }
+
+    fn data(&self) -> &[Color] {
+        self.renderer.data()
+    }
+
+    fn stride(&self) -> usize {
+        self.renderer.stride()
+    }
+
+    /// Anything may change through the returned data, so everything is recorded as changed
+    fn data_mut(&mut self) -> &mut [Color] {
+        self.damage.add_all();
+        self.renderer.data_mut()
+    }
+
+    /// Send the changed rects to the screen
+    fn sync(&mut self) -> bool {
+        self.flush()
+    }
+
+    /// Send the changed rects to the screen
+    fn update(&mut self) -> bool {
+        self.flush()
+    }
+
+    fn update_rects(&mut self, rects: &[(i32, i32, u32, u32)]) -> bool {
+        self.renderer.update_rects(rects)
+    }
+
+    fn mode(&self) -> &Cell<Mode> {
+        self.renderer.mode()
+    }
+
+    fn pixel(&mut self, x: i32, y: i32, color: Color) {
+        self.add(x, y, 1, 1);
+        self.renderer.pixel(x, y, color);
+    }
+
+    fn arc(&mut self, x0: i32, y0: i32, radius: i32, parts: u8, color: Color) {
+        self.add_circle(x0, y0, radius);
+        self.renderer.arc(x0, y0, radius, parts, color);
+    }
+
+    fn circle(&mut self, x0: i32, y0: i32, radius: i32, color: Color) {
+        self.add_circle(x0, y0, radius);
+        self.renderer.circle(x0, y0, radius, color);
+    }
+
+    fn line(&mut self, argx1: i32, argy1: i32, argx2: i32, argy2: i32, color: Color) {
+        self.add_corners(argx1, argy1, argx2, argy2);
+        self.renderer.line(argx1, argy1, argx2, argy2, color);
+    }
+
+    fn set(&mut self, color: Color) {
+        self.damage.add_all();
+        self.renderer.set(color);
+    }
+
+    fn rect(&mut self, x: i32, y: i32, w: u32, h: u32, color: Color) {
+        self.add(x, y, w, h);
+        self.renderer.rect(x, y, w, h, color);
+    }
+
+    #[cfg(feature = "std")]
+    fn box_blur(&mut self, x: i32, y: i32, w: u32, h: u32, r: i32) {
+        self.add(x, y, w, h);
+        self.renderer.box_blur(x, y, w, h, r);
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    #[cfg(feature = "std")]
+    fn box_shadow(
+        &mut self,
+        x: i32,
+        y: i32,
+        w: u32,
+        h: u32,
+        offset_x: i32,
+        offset_y: i32,
+        r: i32,
+        color: Color,
+    ) {
+        let r_abs = r.unsigned_abs();
+        self.add(
+            x - r_abs as i32 + offset_x,
+            y - r_abs as i32 + offset_y,
+            w + 2 * r_abs,
+            h + 2 * r_abs,
+        );
+        self.renderer
+            .box_shadow(x, y, w, h, offset_x, offset_y, r, color);
+    }
+
+    fn image_over(&mut self, start: i32, image_data: &[Color]) {
+        let width = cmp::max(self.renderer.width(), 1);
+        let rows = (image_data.len() as u32).div_ceil(width);
+        self.add(0, start, width, rows);
+        self.renderer.image_over(start, image_data);
+    }
+
+    fn image_opaque(&mut self, start_x: i32, start_y: i32, w: u32, h: u32, image_data: &[Color]) {
+        self.add(start_x, start_y, w, h);
+        self.renderer
+            .image_opaque(start_x, start_y, w, h, image_data);
+    }
+
+    fn image_fast(&mut self, start_x: i32, start_y: i32, w: u32, h: u32, image_data: &[Color]) {
+        self.add(start_x, start_y, w, h);
+        self.renderer.image_fast(start_x, start_y, w, h, image_data);
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    #[cfg(feature = "std")]
+    fn linear_gradient(
+        &mut self,
+        rect_x: i32,
+        rect_y: i32,
+        rect_width: u32,
+        rect_height: u32,
+        start_x: i32,
+        start_y: i32,
+        end_x: i32,
+        end_y: i32,
+        start_color: Color,
+        end_color: Color,
+    ) {
+        self.add(rect_x, rect_y, rect_width, rect_height);
+        self.renderer.linear_gradient(
+            rect_x,
+            rect_y,
+            rect_width,
+            rect_height,
+            start_x,
+            start_y,
+            end_x,
+            end_y,
+            start_color,
+            end_color,
+        );
+    }
+
+    fn rounded_rect(
+        &mut self,
+        x: i32,
+        y: i32,
+        w: u32,
+        h: u32,
+        radius: u32,
+        filled: bool,
+        color: Color,
+    ) {
+        self.add(x, y, w, h);
+        self.renderer
+            .rounded_rect(x, y, w, h, radius, filled, color);
+    }
+
+    #[cfg(feature = "std")]
+    fn wu_line(&mut self, x0: i32, y0: i32, x1: i32, y1: i32, color: Color) {
+        // Antialiasing touches the pixels next to the line
+        self.add_corners(
+            cmp::min(x0, x1) - 1,
+            cmp::min(y0, y1) - 1,
+            cmp::max(x0, x1) + 1,
+            cmp::max(y0, y1) + 1,
+        );
+        self.renderer.wu_line(x0, y0, x1, y1, color);
+    }
+
+    #[cfg(feature = "std")]
+    fn wu_circle(&mut self, x0: i32, y0: i32, radius: i32, color: Color) {
+        self.add_circle(x0, y0, radius.abs() + 1);
+        self.renderer.wu_circle(x0, y0, radius, color);
+    }
+
+    fn lines(&mut self, points: &[[i32; 2]], color: Color) {
+        if let Some(first) = points.first() {
+            let (mut min, mut max) = (*first, *first);
+            for point in points {
+                for c in 0..2 {
+                    min[c] = cmp::min(min[c], point[c]);
+                    max[c] = cmp::max(max[c], point[c]);
+                }
+            }
+            self.add_corners(min[0], min[1], max[0], max[1]);
+        }
+        self.renderer.lines(points, color);
+    }
+
+    fn fill_path(&mut self, graphicspath: &GraphicsPath, color: Color) {
+        self.add_graphics_path(graphicspath);
+        self.renderer.fill_path(graphicspath, color);
+    }
+
+    fn fill_path_pattern(&mut self, graphicspath: &GraphicsPath, pattern: &Pattern) {
+        self.add_graphics_path(graphicspath);
+        self.renderer.fill_path_pattern(graphicspath, pattern);
+    }
+
+    #[cfg(any(feature = "unifont", feature = "unifont-subset"))]
+    fn char(&mut self, x: i32, y: i32, c: char, color: Color) {
+        self.add(x, y, 8, 16);
+        self.renderer.char(x, y, c, color);
+    }
+
+    #[cfg(any(feature = "unifont", feature = "unifont-subset"))]
+    fn text(&mut self, x: i32, y: i32, text: &str, color: Color, style: &TextStyle) {
+        self.text_font(x, y, text, color, &Unifont, style);
+    }
+
+    #[cfg(any(feature = "unifont", feature = "unifont-subset"))]
+    fn text_font(
+        &mut self,
+        x: i32,
+        y: i32,
+        text: &str,
+        color: Color,
+        font: &dyn Font,
+        style: &TextStyle,
+    ) {
+        let bounds = text::draw_text(&mut self.renderer, x, y, text, color, font, style);
+        self.add(bounds.left(), bounds.top(), bounds.width(), bounds.height());
+    }
+
+    fn rect_pattern(&mut self, x: i32, y: i32, w: u32, h: u32, pattern: &Pattern) {
+        self.add(x, y, w, h);
+        self.renderer.rect_pattern(x, y, w, h, pattern);
+    }
+
+    fn circle_pattern(&mut self, x0: i32, y0: i32, radius: u32, pattern: &Pattern) {
+        self.add_circle(x0, y0, radius as i32);
+        self.renderer.circle_pattern(x0, y0, radius, pattern);
+    }
+
+    fn rounded_rect_pattern(
+        &mut self,
+        x: i32,
+        y: i32,
+        w: u32,
+        h: u32,
+        radius: u32,
+        pattern: &Pattern,
+    ) {
+        self.add(x, y, w, h);
+        self.renderer
+            .rounded_rect_pattern(x, y, w, h, radius, pattern);
+    }
+
+    fn image_stride(
+        &mut self,
+        start_x: i32,
+        start_y: i32,
+        w: u32,
+        h: u32,
+        stride: usize,
+        data: &[Color],
+    ) {
+        self.add(start_x, start_y, w, h);
+        self.renderer
+            .image_stride(start_x, start_y, w, h, stride, data);
+    }
+
+    fn image_legacy(&mut self, start_x: i32, start_y: i32, w: u32, h: u32, data: &[Color]) {
+        self.add(start_x, start_y, w, h);
+        self.renderer.image_legacy(start_x, start_y, w, h, data);
+    }
+
+    fn draw_image_scaled(&mut self, src: &ImageRoi, dest: Rect, filter: Filter) {
+        self.add(dest.left(), dest.top(), dest.width(), dest.height());
+        self.renderer.draw_image_scaled(src, dest, filter);
+    }
+
+    fn draw_image_transformed(&mut self, src: &ImageRoi, transform: &Affine, filter: Filter) {
+        let (w, h) = (src.width() as f32, src.height() as f32);
+        let corners = [(0.0, 0.0), (w, 0.0), (0.0, h), (w, h)].map(|(x, y)| {
+            let (x, y) = transform.apply(x, y);
+            [x, y]
+        });
+        self.add_points(corners, 0.0);
+        self.renderer.draw_image_transformed(src, transform, filter);
+    }
+
+    fn rect_f(&mut self, x: f32, y: f32, w: f32, h: f32, color: Color) {
+        self.add_points([[x, y], [x + w, y + h]], 0.0);
+        self.renderer.rect_f(x, y, w, h, color);
+    }
+
+    #[cfg(feature = "std")]
+    fn line_f(&mut self, x0: f32, y0: f32, x1: f32, y1: f32, width: f32, color: Color) {
+        self.add_points([[x0, y0], [x1, y1]], width / 2.0);
+        self.renderer.line_f(x0, y0, x1, y1, width, color);
+    }
+
+    #[cfg(feature = "std")]
+    fn fill_circle_f(&mut self, x: f32, y: f32, radius: f32, color: Color) {
+        self.add_points([[x, y]], radius);
+        self.renderer.fill_circle_f(x, y, radius, color);
+    }
+
+    #[cfg(feature = "std")]
+    fn stroke_circle_f(&mut self, x: f32, y: f32, radius: f32, width: f32, color: Color) {
+        self.add_points([[x, y]], radius + width / 2.0);
+        self.renderer.stroke_circle_f(x, y, radius, width, color);
+    }
+
+    fn fill_path_f(&mut self, path: &Path, color: Color) {
+        self.add_points(path.points.iter().copied(), 0.0);
+        self.renderer.fill_path_f(path, color);
+    }
+
+    #[cfg(feature = "std")]
+    fn stroke_path_f(&mut self, path: &Path, width: f32, color: Color) {
+        self.add_points(path.points.iter().copied(), width / 2.0);
+        self.renderer.stroke_path_f(path, width, color);
+    }
+
+    fn draw_image_f(&mut self, src: &ImageRoi, x: f32, y: f32, w: f32, h: f32, filter: Filter) {
+        self.add_points([[x, y], [x + w, y + h]], 0.0);
+        self.renderer.draw_image_f(src, x, y, w, h, filter);
+    }
+
+    fn fill_triangle(&mut self, vertices: &[Vertex; 3]) {
+        self.add_points(vertices.iter().map(|v| [v.x, v.y]), 0.0);
+        self.renderer.fill_triangle(vertices);
+    }
+
+    fn fill_triangle_textured(
+        &mut self,
+        vertices: &[Vertex; 3],
+        texture: &ImageRoi,
+        filter: Filter,
+        mapping: Mapping,
+    ) {
+        self.add_points(vertices.iter().map(|v| [v.x, v.y]), 0.0);
+        self.renderer
+            .fill_triangle_textured(vertices, texture, filter, mapping);
+    }
+
+    fn fill_mesh(&mut self, vertices: &[Vertex], indices: &[[u32; 3]]) {
+        self.add_points(mesh_points(vertices, indices), 0.0);
+        self.renderer.fill_mesh(vertices, indices);
+    }
+
+    fn fill_mesh_textured(
+        &mut self,
+        vertices: &[Vertex],
+        indices: &[[u32; 3]],
+        texture: &ImageRoi,
+        filter: Filter,
+        mapping: Mapping,
+    ) {
+        self.add_points(mesh_points(vertices, indices), 0.0);
+        self.renderer
+            .fill_mesh_textured(vertices, indices, texture, filter, mapping);
+    }
+
+    fn flood_fill(
+        &mut self,
+        x: i32,
+        y: i32,
+        color: Color,
+        tolerance: u8,
+        connectivity: Connectivity,
+    ) {
+        let bounds = flood::fill(&mut self.renderer, x, y, color, tolerance, connectivity);
+        self.damage.add(bounds);
+    }
+}
+
+/// The positions of the vertices used by the triangles of a mesh
+fn mesh_points<'a>(
+    vertices: &'a [Vertex],
+    indices: &'a [[u32; 3]],
+) -> impl Iterator<Item = [f32; 2]> + 'a {
+    indices
+        .iter()
+        .flatten()
+        .filter_map(move |&i| vertices.get(i as usize))
+        .map(|v| [v.x, v.y])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::Image;
+
+    const WHITE: Color = Color::rgb(255, 255, 255);
+
+    /// Check that drawing records one rect, `expected`, which covers every changed pixel
+    fn check(expected: Rect, draw: impl FnOnce(&mut DamageTracker<Image>)) {
+        let mut tracker = DamageTracker::new(Image::new(64, 64));
+        tracker.inner_mut().rect(40, 40, 10, 10, WHITE);
+        tracker.damage_mut().clear();
+        let before = tracker.inner().data().to_vec();
+        draw(&mut tracker);
+
+        assert!(!tracker.damage().is_all());
+        let parts = |r: &Rect| (r.left(), r.top(), r.width(), r.height());
+        let rects: Vec<_> = tracker.damage().rects().iter().map(parts).collect();
+        assert_eq!(rects, [parts(&expected)]);
+        for (i, (old, new)) in before.iter().zip(tracker.inner().data()).enumerate() {
+            let (x, y) = ((i % 64) as i32, (i / 64) as i32);
+            if old.data != new.data {
+                assert!(
+                    expected.contains(x, y),
+                    "({}, {}) outside {:?}",
+                    x,
+                    y,
+                    expected
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn integer_primitives() {
+        let red = Color::rgb(255, 0, 0);
+        check(Rect::new(3, 4, 1, 1), |t| t.pixel(3, 4, red));
+        check(Rect::new(10, 10, 5, 6), |t| t.rect(10, 10, 5, 6, red));
+        check(Rect::new(0, 60, 15, 4), |t| t.rect(-5, 60, 20, 10, red));
+        check(Rect::new(1, 2, 8, 4), |t| t.line(8, 5, 1, 2, red));
+        check(Rect::new(1, 2, 8, 9), |t| {
+            t.lines(&[[1, 2], [8, 5], [4, 10]], red)
+        });
+        check(Rect::new(20, 20, 4, 4), |t| {
+            t.image(20, 20, 4, 4, &[red; 16])
+        });
+        check(Rect::new(62, 62, 2, 2), |t| {
+            t.image_fast(62, 62, 4, 4, &[red; 16])
+        });
+        check(Rect::new(40, 40, 10, 10), |t| {
+            t.flood_fill(45, 45, red, 0, Connectivity::Four)
+        });
+        let mut path = GraphicsPath::new();
+        path.move_to(5, 5);
+        path.line_to(15, 8);
+        path.line_to(7, 20);
+        check(Rect::new(5, 5, 10, 15), |t| t.fill_path(&path, red));
+    }
+
+    #[test]
+    fn image_primitives() {
+        let image = Image::from_color(8, 8, Color::rgb(0, 0, 255));
+        let src = image.roi(&Rect::new(0, 0, 8, 8));
+        check(Rect::new(60, 0, 4, 6), |t| src.draw(t, 60, -2));
+        check(Rect::new(30, 30, 10, 5), |t| {
+            t.draw_image_scaled(&src, Rect::new(30, 30, 10, 5), Filter::Nearest)
+        });
+        check(Rect::new(8, 5, 16, 8), |t| {
+            let transform = Affine::translate(4.0, 5.0).then(&Affine::scale(2.0, 1.0));
+            t.draw_image_transformed(&src, &transform, Filter::Nearest)
+        });
+        check(Rect::new(1, 2, 5, 3), |t| {
+            t.draw_image_f(&src, 1.5, 2.25, 4.0, 2.5, Filter::Bilinear)
+        });
+    }
+
+    #[test]
+    fn fractional_primitives() {
+        let green = Color::rgba(0, 255, 0, 200);
+        check(Rect::new(1, 2, 4, 2), |t| {
+            t.rect_f(1.5, 2.25, 3.0, 1.5, green)
+        });
+        check(Rect::new(10, 10, 10, 10), |t| {
+            t.fill_triangle(&[
+                Vertex::new(10.0, 10.0, green),
+                Vertex::new(20.0, 10.0, green),
+                Vertex::new(10.0, 20.0, green),
+            ])
+        });
+        let vertices = [
+            Vertex::new(10.0, 10.0, green),
+            Vertex::new(20.0, 10.0, green),
+            Vertex::new(10.0, 20.0, green),
+            Vertex::new(30.0, 30.0, green),
+        ];
+        check(Rect::new(10, 10, 10, 10), |t| {
+            t.fill_mesh(&vertices, &[[0, 1, 2], [0, 1, 9]])
+        });
+        let mut path = Path::new();
+        path.move_to(2.5, 3.5);
+        path.line_to(12.0, 4.0);
+        path.line_to(6.0, 9.25);
+        path.close();
+        check(Rect::new(2, 3, 10, 7), |t| t.fill_path_f(&path, green));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn std_primitives() {
+        let green = Color::rgba(0, 255, 0, 200);
+        check(Rect::new(9, 9, 12, 2), |t| {
+            t.line_f(10.0, 10.0, 20.0, 10.0, 2.0, green)
+        });
+        check(Rect::new(27, 27, 10, 10), |t| {
+            t.fill_circle_f(32.0, 32.0, 5.0, green)
+        });
+        check(Rect::new(26, 26, 12, 12), |t| {
+            t.stroke_circle_f(32.0, 32.0, 5.0, 2.0, green)
+        });
+        check(Rect::new(5, 6, 10, 10), |t| {
+            t.linear_gradient(5, 6, 10, 10, 5, 6, 15, 16, green, WHITE)
+        });
+    }
+
+    #[cfg(any(feature = "unifont", feature = "unifont-subset"))]
+    #[test]
+    fn text_primitives() {
+        let blue = Color::rgb(0, 0, 255);
+        check(Rect::new(5, 5, 8, 16), |t| t.char(5, 5, 'A', blue));
+        check(Rect::new(5, 5, 16, 16), |t| {
+            t.text(5, 5, "Hi", blue, &TextStyle::default())
+        });
+    }
+}
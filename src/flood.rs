@@ -0,0 +1,262 @@
+// SPDX-License-Identifier: MIT
+
+//! Finding the area of similar colors connected to a pixel, to fill it like a paint bucket or
+//! select it like a magic wand.
+
+#[cfg(not(feature = "std"))]
+use alloc::vec;
+
+use crate::color::Color;
+use crate::gray::GrayImage;
+use crate::rect::Rect;
+use crate::renderer::Renderer;
+
+/// Which neighbors of a pixel are connected to it
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Connectivity {
+    /// The pixels left, right, above and below
+    #[default]
+    Four,
+    /// The pixels around it, including diagonally, so areas leak through diagonal gaps
+    Eight,
+}
+
+/// Whether every channel of two colors, including alpha, differs by at most `tolerance`
+fn matches(a: Color, b: Color, tolerance: u8) -> bool {
+    a.data
+        .to_le_bytes()
+        .iter()
+        .zip(b.data.to_le_bytes().iter())
+        .all(|(&a, &b)| a.abs_diff(b) <= tolerance)
+}
+
+/// The pixels connected to (x, y) with colors within `tolerance` of its color, as 255 in a
+/// mask of the renderer's size. The mask is empty when (x, y) is outside of the renderer.
+///
+/// Whole runs of a row are filled at once, and only the start of each run above and below is
+/// remembered, so large areas need little memory and no recursion.
+pub(crate) fn region<R: Renderer + ?Sized>(
+    renderer: &R,
+    x: i32,
+    y: i32,
+    tolerance: u8,
+    connectivity: Connectivity,
+) -> GrayImage {
+    let (w, h) = (renderer.width() as usize, renderer.height() as usize);
+    let mut mask = vec![0u8; w * h];
+    if x < 0 || y < 0 || x as usize >= w || y as usize >= h {
+        return GrayImage::from_data(w as u32, h as u32, mask.into_boxed_slice())
+            .expect("mask has w * h values");
+    }
+
+    let (data, stride) = (renderer.data(), renderer.stride());
+    let seed = data[y as usize * stride + x as usize];
+    let fillable = |mask: &[u8], x: usize, y: usize| {
+        mask[y * w + x] == 0 && matches(data[y * stride + x], seed, tolerance)
+    };
+    let reach = match connectivity {
+        Connectivity::Four => 0,
+        Connectivity::Eight => 1,
+    };
+
+    let mut stack = vec![(x as usize, y as usize)];
+    while let Some((x, y)) = stack.pop() {
+        if !fillable(&mask, x, y) {
+            continue;
+        }
+        let mut left = x;
+        while left > 0 && fillable(&mask, left - 1, y) {
+            left -= 1;
+        }
+        let mut right = x;
+        while right + 1 < w && fillable(&mask, right + 1, y) {
+            right += 1;
+        }
+        let row = y * w;
+        mask[row + left..=row + right].fill(255);
+
+        // Remember the start of each run next to this one in the rows above and below
+        let first = left.saturating_sub(reach);
+        let last = (right + reach).min(w - 1);
+        for next_y in [y.wrapping_sub(1), y + 1] {
+            if next_y >= h {
+                continue;
+            }
+            let mut in_run = false;
+            for next_x in first..=last {
+                let fill = fillable(&mask, next_x, next_y);
+                if fill && !in_run {
+                    stack.push((next_x, next_y));
+                }
+                in_run = fill;
+            }
+        }
+    }
+
+    GrayImage::from_data(w as u32, h as u32, mask.into_boxed_slice())
+        .expect("mask has w * h values")
+}
+
+/// Fill the pixels connected to (x, y) with colors within `tolerance` of its color, returning
+/// the bounds of the filled pixels
+pub(crate) fn fill<R: Renderer + ?Sized>(
+    renderer: &mut R,
+    x: i32,
+    y: i32,
+    color: Color,
+    tolerance: u8,
+    connectivity: Connectivity,
+) -> Rect {
+    let mask = region(renderer, x, y, tolerance, connectivity);
+    let w = mask.width() as usize;
+    let mut bounds = Rect::default();
+    if w == 0 {
+        return bounds;
+    }
+    for (y, row) in mask.data().chunks_exact(w).enumerate() {
+        let mut start = None;
+        for (x, &value) in row.iter().chain(Some(&0)).enumerate() {
+            match (value != 0, start) {
+                (true, None) => start = Some(x),
+                (false, Some(left)) => {
+                    let run = Rect::new(left as i32, y as i32, (x - left) as u32, 1);
+                    renderer.rect(run.left(), run.top(), run.width(), 1, color);
+                    bounds = if bounds.is_empty() {
+                        run
+                    } else {
+                        bounds.container(&run)
+                    };
+                    start = None;
+                }
+                _ => (),
+            }
+        }
+    }
+    bounds
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(not(feature = "std"))]
+    use alloc::vec::Vec;
+
+    use super::*;
+    use crate::image::{Image, ImageRef};
+    use crate::Mode;
+
+    const WHITE: Color = Color::rgb(255, 255, 255);
+    const BLACK: Color = Color::rgb(0, 0, 0);
+    const RED: Color = Color::rgb(255, 0, 0);
+
+    fn tuple(rect: Rect) -> (i32, i32, u32, u32) {
+        (rect.left(), rect.top(), rect.width(), rect.height())
+    }
+
+    fn count(mask: &GrayImage) -> usize {
+        mask.data().iter().filter(|&&value| value == 255).count()
+    }
+
+    /// A white image split by a black diagonal from the bottom left to the top right
+    fn diagonal() -> Image {
+        let mut image = Image::from_color(6, 6, WHITE);
+        for i in 0..6 {
+            image.pixel(i, 5 - i, BLACK);
+        }
+        image
+    }
+
+    #[test]
+    fn diagonal_gap() {
+        let image = diagonal();
+        let four = region(&image, 0, 0, 0, Connectivity::Four);
+        assert_eq!(count(&four), 15);
+        assert_eq!(four.get(4, 0), Some(255));
+        assert_eq!(four.get(5, 5), Some(0));
+
+        let eight = region(&image, 0, 0, 0, Connectivity::Eight);
+        assert_eq!(count(&eight), 30);
+        assert_eq!(eight.get(5, 5), Some(255));
+        assert_eq!(eight.get(0, 5), Some(0));
+    }
+
+    #[test]
+    fn tolerance() {
+        let mut image = Image::new(8, 3);
+        for x in 0..8 {
+            image.rect(x, 0, 1, 3, Color::rgb(x as u8 * 10, 0, 0));
+        }
+        // Every channel is compared, including alpha
+        image.mode().set(Mode::Overwrite);
+        image.pixel(1, 2, Color::rgba(10, 0, 0, 200));
+        let columns = |tolerance| {
+            let mask = region(&image, 0, 0, tolerance, Connectivity::Four);
+            (count(&mask), mask.get(1, 2))
+        };
+        assert_eq!(columns(0), (3, Some(0)));
+        assert_eq!(columns(9), (3, Some(0)));
+        assert_eq!(columns(10), (5, Some(0)));
+        assert_eq!(columns(25), (8, Some(0)));
+        assert_eq!(columns(55), (18, Some(255)));
+    }
+
+    #[test]
+    fn seed_outside() {
+        let mut image = diagonal();
+        for &(x, y) in &[(-1, 0), (0, -1), (6, 0), (0, 6), (i32::MIN, i32::MAX)] {
+            let mask = region(&image, x, y, 255, Connectivity::Eight);
+            assert_eq!((mask.width(), mask.height()), (6, 6));
+            assert_eq!(count(&mask), 0);
+            let bounds = fill(&mut image, x, y, RED, 255, Connectivity::Eight);
+            assert_eq!(tuple(bounds), tuple(Rect::default()));
+        }
+        assert!(image
+            .data()
+            .iter()
+            .zip(diagonal().data())
+            .all(|(a, b)| a.data == b.data));
+    }
+
+    #[test]
+    fn padded_rows() {
+        // A black column splits 4 pixels of each row, padded to 6 with white
+        let (w, h, stride) = (4, 5, 6);
+        let mut data = vec![WHITE; stride * h as usize];
+        for row in data.chunks_mut(stride) {
+            row[2] = BLACK;
+        }
+        let mut image = ImageRef::from_data_with_stride(w, h, stride, &mut data).unwrap();
+        let mask = region(&image, 0, 0, 0, Connectivity::Eight);
+        assert_eq!((mask.width(), mask.height()), (w, h));
+        assert_eq!(count(&mask), 10);
+        assert_eq!(mask.get(3, 0), Some(0));
+
+        let bounds = fill(&mut image, 3, 4, RED, 0, Connectivity::Four);
+        assert_eq!(tuple(bounds), (3, 0, 1, 5));
+        for row in data.chunks(stride) {
+            let colors: Vec<u32> = row.iter().map(|color| color.data).collect();
+            assert_eq!(
+                colors,
+                [WHITE, WHITE, BLACK, RED, WHITE, WHITE].map(|color| color.data)
+            );
+        }
+    }
+
+    #[test]
+    fn bounds() {
+        let mut image = diagonal();
+        let bounds = fill(&mut image, 0, 0, RED, 0, Connectivity::Four);
+        assert_eq!(tuple(bounds), (0, 0, 5, 5));
+        assert_eq!(image.getpixel(4, 0).data, RED.data);
+        assert_eq!(image.getpixel(0, 4).data, RED.data);
+        assert_eq!(image.getpixel(1, 4).data, BLACK.data);
+
+        let bounds = fill(&mut image, 5, 5, RED, 0, Connectivity::Four);
+        assert_eq!(tuple(bounds), (1, 1, 5, 5));
+
+        // A single pixel
+        let mut image = Image::from_color(4, 4, WHITE);
+        image.pixel(2, 1, BLACK);
+        let bounds = fill(&mut image, 2, 1, RED, 0, Connectivity::Eight);
+        assert_eq!(tuple(bounds), (2, 1, 1, 1));
+    }
+}
@@ -0,0 +1,124 @@
+// SPDX-License-Identifier: MIT
+
+//! Lookup of the embedded Unifont glyph bitmaps.
+//!
+//! With the `unifont` feature the whole Basic Multilingual Plane is embedded.
+//! The `unifont-subset` feature embeds only the Unicode ranges listed in the
+//! `ORBCLIENT_UNIFONT_RANGES` environment variable at build time, for example
+//! `ORBCLIENT_UNIFONT_RANGES="basic-latin,latin-1,2500-257F"`.
+//! Characters that are not embedded are drawn as [`REPLACEMENT`], except by
+//! [`Renderer::char`](crate::Renderer::char) with the full font, which draws them blank.
+
+use core::convert::TryInto;
+
+/// Width of a glyph in pixels
+pub const GLYPH_WIDTH: u32 = 8;
+
+/// Height of a glyph in pixels
+pub const GLYPH_HEIGHT: u32 = 16;
+
+/// The character drawn in place of characters missing from the font
+pub const REPLACEMENT: char = '\u{FFFD}';
+
+/// A glyph bitmap, one byte per row with the most significant bit on the left
+pub type Glyph = [u8; GLYPH_HEIGHT as usize];
+
+#[cfg(all(feature = "unifont-subset", not(feature = "unifont")))]
+mod subset {
+    include!(concat!(env!("OUT_DIR"), "/unifont-subset.rs"));
+
+    pub(super) static GLYPHS: &[u8] =
+        include_bytes!(concat!(env!("OUT_DIR"), "/unifont-subset.font"));
+}
+
+/// One bit per codepoint of the Basic Multilingual Plane, set for the glyphs the full font
+/// has, as it stores the others as empty bitmaps just like blank ones
+#[cfg(feature = "unifont")]
+static DEFINED: &[u8] = include_bytes!("../res/unifont.defined");
+
+/// Get the bitmap of a character, if it is embedded
+#[cfg(feature = "unifont")]
+pub fn glyph(c: char) -> Option<&'static Glyph> {
+    let c = c as usize;
+    if DEFINED.get(c / 8)? & (1 << (c % 8)) == 0 {
+        return None;
+    }
+    let offset = c * GLYPH_HEIGHT as usize;
+    crate::FONT
+        .get(offset..offset + GLYPH_HEIGHT as usize)?
+        .try_into()
+        .ok()
+}
+
+/// Get the bitmap of a character, if it is embedded
+#[cfg(all(feature = "unifont-subset", not(feature = "unifont")))]
+pub fn glyph(c: char) -> Option<&'static Glyph> {
+    use core::cmp::Ordering;
+
+    let c = c as u32;
+    let i = subset::RANGES
+        .binary_search_by(|&(start, end, _)| {
+            if end < c {
+                Ordering::Less
+            } else if start > c {
+                Ordering::Greater
+            } else {
+                Ordering::Equal
+            }
+        })
+        .ok()?;
+    let (start, _, index) = subset::RANGES[i];
+    let offset = (index + c - start) as usize * GLYPH_HEIGHT as usize;
+    subset::GLYPHS
+        .get(offset..offset + GLYPH_HEIGHT as usize)?
+        .try_into()
+        .ok()
+}
+
+/// Get the bitmap of a character, or of [`REPLACEMENT`] if it is not embedded
+pub fn glyph_or_replacement(c: char) -> &'static Glyph {
+    glyph(c).or_else(|| glyph(REPLACEMENT)).unwrap_or(&EMPTY)
+}
+
+static EMPTY: Glyph = [0; GLYPH_HEIGHT as usize];
+
+/// The bitmap drawn by `Renderer::char`, blank for characters the full font does not have
+#[cfg(feature = "unifont")]
+pub(crate) fn char_glyph(c: char) -> &'static Glyph {
+    let offset = c as usize * GLYPH_HEIGHT as usize;
+    crate::FONT
+        .get(offset..offset + GLYPH_HEIGHT as usize)
+        .and_then(|glyph| glyph.try_into().ok())
+        .unwrap_or(&EMPTY)
+}
+
+/// The bitmap drawn by `Renderer::char`, [`REPLACEMENT`] for characters not in the subset
+#[cfg(all(feature = "unifont-subset", not(feature = "unifont")))]
+pub(crate) fn char_glyph(c: char) -> &'static Glyph {
+    glyph_or_replacement(c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "unifont")]
+    #[test]
+    fn char_glyph_full() {
+        assert_eq!(char_glyph('A'), glyph('A').unwrap());
+        // Private use and outside of the Basic Multilingual Plane, not in the font
+        assert!(glyph('\u{E000}').is_none());
+        assert_eq!(char_glyph('\u{E000}'), &EMPTY);
+        assert!(glyph('\u{1F600}').is_none());
+        assert_eq!(char_glyph('\u{1F600}'), &EMPTY);
+    }
+
+    #[cfg(all(feature = "unifont-subset", not(feature = "unifont")))]
+    #[test]
+    fn char_glyph_subset() {
+        assert_eq!(char_glyph('A'), glyph('A').unwrap());
+        assert!(glyph('\u{E000}').is_none());
+        assert_eq!(char_glyph('\u{E000}'), glyph(REPLACEMENT).unwrap());
+        assert_ne!(char_glyph('\u{E000}'), &EMPTY);
+    }
+}
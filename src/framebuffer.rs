@@ -0,0 +1,392 @@
+// SPDX-License-Identifier: MIT
+
+//! Drawing into framebuffers that are not tightly packed ARGB, like the ones handed out by
+//! bootloaders, UEFI GOP, fbdev or DRM dumb buffers.
+
+#[cfg(not(feature = "std"))]
+use alloc::{vec, vec::Vec};
+
+use core::cell::Cell;
+use core::mem;
+
+use crate::color::Color;
+use crate::rect::Rect;
+use crate::renderer::Renderer;
+use crate::Mode;
+
+/// Layout of a pixel in memory. Names follow the DRM fourcc formats: components are listed
+/// from the most significant bit of a little endian word, so `Xrgb8888` is stored as
+/// blue, green, red, unused.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PixelFormat {
+    /// The layout of [`Color`]
+    Argb8888,
+    /// The layout of [`Color`] with the alpha byte unused, as in UEFI GOP `BlueGreenRedReserved`
+    Xrgb8888,
+    Abgr8888,
+    /// UEFI GOP `RedGreenBlueReserved`
+    Xbgr8888,
+    Rgb565,
+    Rgb888,
+    Bgr888,
+}
+
+impl PixelFormat {
+    pub const fn bytes_per_pixel(self) -> usize {
+        match self {
+            PixelFormat::Argb8888
+            | PixelFormat::Xrgb8888
+            | PixelFormat::Abgr8888
+            | PixelFormat::Xbgr8888 => 4,
+            PixelFormat::Rgb888 | PixelFormat::Bgr888 => 3,
+            PixelFormat::Rgb565 => 2,
+        }
+    }
+
+    /// Write a color to the bytes of one pixel, dropping alpha if the format has none
+    pub fn encode(self, color: Color, pixel: &mut [u8]) {
+        let (r, g, b, a) = (color.r(), color.g(), color.b(), color.a());
+        match self {
+            PixelFormat::Argb8888 => pixel[..4].copy_from_slice(&color.data.to_le_bytes()),
+            PixelFormat::Xrgb8888 => pixel[..4].copy_from_slice(&[b, g, r, 0xFF]),
+            PixelFormat::Abgr8888 => pixel[..4].copy_from_slice(&[r, g, b, a]),
+            PixelFormat::Xbgr8888 => pixel[..4].copy_from_slice(&[r, g, b, 0xFF]),
+            PixelFormat::Rgb565 => {
+                let value = ((r as u16 >> 3) << 11) | ((g as u16 >> 2) << 5) | (b as u16 >> 3);
+                pixel[..2].copy_from_slice(&value.to_le_bytes());
+            }
+            PixelFormat::Rgb888 => pixel[..3].copy_from_slice(&[b, g, r]),
+            PixelFormat::Bgr888 => pixel[..3].copy_from_slice(&[r, g, b]),
+        }
+    }
+
+    /// Read a color from the bytes of one pixel, solid if the format has no alpha
+    pub fn decode(self, pixel: &[u8]) -> Color {
+        match self {
+            PixelFormat::Argb8888 => Color {
+                data: u32::from_le_bytes([pixel[0], pixel[1], pixel[2], pixel[3]]),
+            },
+            PixelFormat::Xrgb8888 => Color::rgb(pixel[2], pixel[1], pixel[0]),
+            PixelFormat::Abgr8888 => Color::rgba(pixel[0], pixel[1], pixel[2], pixel[3]),
+            PixelFormat::Xbgr8888 | PixelFormat::Bgr888 => Color::rgb(pixel[0], pixel[1], pixel[2]),
+            PixelFormat::Rgb565 => {
+                let value = u16::from_le_bytes([pixel[0], pixel[1]]);
+                let r = (value >> 11) as u8 & 0x1F;
+                let g = (value >> 5) as u8 & 0x3F;
+                let b = value as u8 & 0x1F;
+                Color::rgb(
+                    (r << 3) | (r >> 2),
+                    (g << 2) | (g >> 4),
+                    (b << 3) | (b >> 2),
+                )
+            }
+            PixelFormat::Rgb888 => Color::rgb(pixel[2], pixel[1], pixel[0]),
+        }
+    }
+}
+
+enum Pixels<'a> {
+    /// The buffer already has the layout of a `Color` slice, with rows `stride / 4` apart
+    Direct(&'a mut [Color]),
+    /// Drawing goes to `image`, which is converted into `buffer` on `sync` and `update_rects`
+    Shadow {
+        buffer: &'a mut [u8],
+        image: Vec<Color>,
+    },
+}
+
+/// A renderer for a framebuffer with a row stride and pixel format of its own.
+///
+/// When the framebuffer is `Argb8888` or `Xrgb8888` on a little endian target, with rows
+/// aligned to whole pixels, it is drawn into directly, skipping the padding after each row.
+/// Otherwise drawing goes to a back buffer, like a window, and `sync` or `update_rects`
+/// convert it into the framebuffer.
+pub struct Framebuffer<'a> {
+    w: u32,
+    h: u32,
+    stride: usize,
+    format: PixelFormat,
+    pixels: Pixels<'a>,
+    mode: Cell<Mode>,
+}
+
+impl<'a> Framebuffer<'a> {
+    /// Wrap `h` rows of `w` pixels, each row starting `stride` bytes after the previous one.
+    /// Returns `None` if rows overlap or the buffer is too small.
+    // `is_multiple_of` would need Rust 1.87
+    #[allow(clippy::manual_is_multiple_of)]
+    pub fn new(
+        buffer: &'a mut [u8],
+        w: u32,
+        h: u32,
+        stride: usize,
+        format: PixelFormat,
+    ) -> Option<Self> {
+        let row_len = (w as usize).checked_mul(format.bytes_per_pixel())?;
+        if stride < row_len {
+            return None;
+        }
+        if h > 0 && buffer.len() < (h as usize - 1).checked_mul(stride)?.checked_add(row_len)? {
+            return None;
+        }
+
+        let direct = cfg!(target_endian = "little")
+            && matches!(format, PixelFormat::Argb8888 | PixelFormat::Xrgb8888)
+            && stride % mem::size_of::<Color>() == 0
+            && buffer.as_ptr().align_offset(mem::align_of::<Color>()) == 0;
+        let pixels = if direct {
+            // Safe as `Color` is a transparent `u32`, for which every bit pattern is valid
+            let (_, colors, _) = unsafe { buffer.align_to_mut::<Color>() };
+            let len = match h {
+                0 => 0,
+                h => (h as usize - 1) * (stride / mem::size_of::<Color>()) + w as usize,
+            };
+            Pixels::Direct(&mut colors[..len])
+        } else {
+            let mut image = vec![Color::rgb(0, 0, 0); w as usize * h as usize];
+            for (y, row) in image.chunks_exact_mut(w.max(1) as usize).enumerate() {
+                let line = &buffer[y * stride..];
+                for (x, color) in row.iter_mut().enumerate() {
+                    *color = format.decode(&line[x * format.bytes_per_pixel()..]);
+                }
+            }
+            Pixels::Shadow { buffer, image }
+        };
+
+        Some(Framebuffer {
+            w,
+            h,
+            stride,
+            format,
+            pixels,
+            mode: Cell::new(Mode::Blend),
+        })
+    }
+
+    pub fn format(&self) -> PixelFormat {
+        self.format
+    }
+
+    /// Bytes from the start of one row to the next
+    pub fn pitch(&self) -> usize {
+        self.stride
+    }
+
+    /// Whether drawing goes straight to the framebuffer, with nothing to convert
+    pub fn is_direct(&self) -> bool {
+        matches!(self.pixels, Pixels::Direct(_))
+    }
+
+    /// Convert a region of the back buffer into the framebuffer
+    fn present(&mut self, rect: Rect) {
+        let rect = rect.intersection(&Rect::new(0, 0, self.w, self.h));
+        let (w, stride, format) = (self.w as usize, self.stride, self.format);
+        if let Pixels::Shadow { buffer, image } = &mut self.pixels {
+            let bpp = format.bytes_per_pixel();
+            for y in rect.top() as usize..rect.bottom() as usize {
+                for x in rect.left() as usize..rect.right() as usize {
+                    format.encode(image[y * w + x], &mut buffer[y * stride + x * bpp..]);
+                }
+            }
+        }
+    }
+}
+
+impl Renderer for Framebuffer<'_> {
+    fn width(&self) -> u32 {
+        self.w
+    }
+
+    fn height(&self) -> u32 {
+        self.h
+    }
+
+    fn data(&self) -> &[Color] {
+        match &self.pixels {
+            Pixels::Direct(data) => data,
+            Pixels::Shadow { image, .. } => image,
+        }
+    }
+
+    fn data_mut(&mut self) -> &mut [Color] {
+        match &mut self.pixels {
+            Pixels::Direct(data) => data,
+            Pixels::Shadow { image, .. } => image,
+        }
+    }
+
+    fn stride(&self) -> usize {
+        match self.pixels {
+            Pixels::Direct(_) => self.stride / mem::size_of::<Color>(),
+            Pixels::Shadow { .. } => self.w as usize,
+        }
+    }
+
+    fn mode(&self) -> &Cell<Mode> {
+        &self.mode
+    }
+
+    fn sync(&mut self) -> bool {
+        self.present(Rect::new(0, 0, self.w, self.h));
+        true
+    }
+
+    fn update(&mut self) -> bool {
+        self.sync()
+    }
+
+    fn update_rects(&mut self, rects: &[(i32, i32, u32, u32)]) -> bool {
+        for &(x, y, w, h) in rects {
+            self.present(Rect::new(x, y, w, h));
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(not(feature = "std"))]
+    use alloc::format;
+
+    use super::*;
+
+    const FORMATS: [PixelFormat; 7] = [
+        PixelFormat::Argb8888,
+        PixelFormat::Xrgb8888,
+        PixelFormat::Abgr8888,
+        PixelFormat::Xbgr8888,
+        PixelFormat::Rgb565,
+        PixelFormat::Rgb888,
+        PixelFormat::Bgr888,
+    ];
+
+    const PADDING: u8 = 0xA5;
+
+    #[test]
+    fn padded_rows() {
+        let (w, h) = (5, 4);
+        let red = Color::rgb(255, 0, 0);
+        let green = Color::rgb(0, 255, 0);
+        let blue = Color::rgb(0, 0, 255);
+        let image = [blue; 4];
+        let mut expected = vec![red; w * h];
+        expected[2 * w + 3] = green;
+        for y in 1..3 {
+            for x in 3..5 {
+                expected[y * w + x] = blue;
+            }
+        }
+
+        for format in FORMATS {
+            let bpp = format.bytes_per_pixel();
+            for padding in [0, 3, 8] {
+                let stride = w * bpp + padding;
+                // Backed by words so that 32-bit formats are aligned to be drawn into directly
+                let mut words = vec![0u32; (h * stride).div_ceil(4)];
+                let (_, buffer, _) = unsafe { words.align_to_mut::<u8>() };
+                buffer.fill(PADDING);
+
+                let mut fb = Framebuffer::new(buffer, w as u32, h as u32, stride, format).unwrap();
+                let direct = matches!(format, PixelFormat::Argb8888 | PixelFormat::Xrgb8888)
+                    && padding % 4 == 0;
+                assert_eq!(fb.is_direct(), direct, "{:?} {}", format, padding);
+                fb.set(red);
+                fb.pixel(3, 2, green);
+                fb.mode().set(Mode::Overwrite);
+                fb.image(3, 1, 2, 2, &image);
+                assert_eq!(fb.getpixel(3, 2), blue);
+                fb.sync();
+
+                for y in 0..h {
+                    let row = &buffer[y * stride..(y + 1) * stride];
+                    for x in 0..w {
+                        let mut pixel = [0; 4];
+                        format.encode(expected[y * w + x], &mut pixel);
+                        let at = format!("{:?} {} ({}, {})", format, padding, x, y);
+                        if direct {
+                            // The unused alpha byte of Xrgb8888 is written as is
+                            assert_eq!(row[x * bpp..x * bpp + 3], pixel[..3], "{}", at);
+                        } else {
+                            assert_eq!(row[x * bpp..(x + 1) * bpp], pixel[..bpp], "{}", at);
+                        }
+                    }
+                    assert!(row[w * bpp..].iter().all(|&b| b == PADDING));
+                }
+            }
+        }
+    }
+
+    /// Draw with the methods that index the pixel buffer themselves
+    fn draw<R: Renderer>(renderer: &mut R) {
+        renderer.set(Color::rgb(20, 40, 60));
+        renderer.rect(-3, 2, 9, 5, Color::rgba(200, 100, 0, 128));
+        renderer.pixel(7, 1, Color::rgb(255, 255, 255));
+        renderer.image(9, 6, 4, 4, &[Color::rgba(0, 0, 255, 200); 16]);
+        #[cfg(feature = "std")]
+        {
+            renderer.linear_gradient(
+                1,
+                5,
+                8,
+                6,
+                0,
+                0,
+                10,
+                12,
+                Color::rgb(0, 0, 0),
+                Color::rgb(255, 0, 255),
+            );
+            renderer.box_blur(0, 0, 6, 6, 2);
+        }
+        let fill = renderer.getpixel(0, 11);
+        renderer.flood_fill(
+            0,
+            11,
+            Color::rgb(fill.g(), fill.b(), fill.r()),
+            0,
+            Default::default(),
+        );
+    }
+
+    #[test]
+    fn padded_direct_matches_image() {
+        let (w, h, stride) = (11, 12, 64);
+        let mut words = vec![0u32; h * stride / 4];
+        let (_, buffer, _) = unsafe { words.align_to_mut::<u8>() };
+        let mut fb =
+            Framebuffer::new(buffer, w as u32, h as u32, stride, PixelFormat::Argb8888).unwrap();
+        assert!(fb.is_direct());
+        let mut image = crate::image::Image::new(w as u32, h as u32);
+        draw(&mut fb);
+        draw(&mut image);
+        for y in 0..h as i32 {
+            for x in 0..w as i32 {
+                assert_eq!(
+                    fb.getpixel(x, y).data,
+                    image.getpixel(x, y).data,
+                    "({}, {})",
+                    x,
+                    y
+                );
+            }
+        }
+        drop(fb);
+        assert!(words
+            .chunks(stride / 4)
+            .all(|row| row[w..].iter().all(|&p| p == 0)));
+    }
+
+    #[test]
+    fn decode_encode() {
+        let color = Color::rgb(0x12, 0x84, 0xF8);
+        for format in FORMATS {
+            let mut pixel = [0; 4];
+            format.encode(color, &mut pixel);
+            let decoded = format.decode(&pixel);
+            let close = |a: u8, b: u8| a.abs_diff(b) <= 7;
+            assert!(close(decoded.r(), color.r()), "{:?}", format);
+            assert!(close(decoded.g(), color.g()), "{:?}", format);
+            assert!(close(decoded.b(), color.b()), "{:?}", format);
+        }
+    }
+}
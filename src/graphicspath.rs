@@ -3,6 +3,9 @@
 #[cfg(not(feature = "std"))]
 use alloc::vec::Vec;
 
+use crate::math;
+use crate::rect::Rect;
+
 /// point type (is the point a new position or a connection point)
 pub enum PointType {
     Move,
@@ -117,4 +120,66 @@ impl GraphicsPath {
         self.x = argx3;
         self.y = argy3;
     }
+
+    /// Call `span(x, y, w)` for each horizontal run of pixels in `clip` inside the path,
+    /// with the nonzero winding rule. Every subpath is closed.
+    pub(crate) fn fill_spans(&self, clip: &Rect, mut span: impl FnMut(i32, i32, u32)) {
+        let mut edges = Vec::new();
+        let mut start = None;
+        let mut last = (0, 0);
+        for &(x, y, ref point_type) in self.points.iter() {
+            match (point_type, start) {
+                (PointType::Connect, Some(_)) => edges.push((last, (x, y))),
+                _ => {
+                    if let Some(start) = start {
+                        edges.push((last, start));
+                    }
+                    start = Some((x, y));
+                }
+            }
+            last = (x, y);
+        }
+        if let Some(start) = start {
+            edges.push((last, start));
+        }
+        edges.retain(|(a, b)| a.1 != b.1);
+
+        let min_y = edges.iter().map(|(a, b)| a.1.min(b.1)).min();
+        let max_y = edges.iter().map(|(a, b)| a.1.max(b.1)).max();
+        let (Some(min_y), Some(max_y)) = (min_y, max_y) else {
+            return;
+        };
+
+        // Where the edges cross the center of a row, with their direction
+        let mut crossings: Vec<(f32, i32)> = Vec::new();
+        for y in min_y.max(clip.top())..max_y.min(clip.bottom()) {
+            let center = y as f32 + 0.5;
+            crossings.clear();
+            for &((x0, y0), (x1, y1)) in edges.iter() {
+                if (y0.min(y1) as f32) <= center && center < (y0.max(y1) as f32) {
+                    let t = (center - y0 as f32) / (y1 - y0) as f32;
+                    let direction = if y1 > y0 { 1 } else { -1 };
+                    crossings.push((x0 as f32 + t * (x1 - x0) as f32, direction));
+                }
+            }
+            crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+            let mut winding = 0;
+            let mut span_start = 0.0;
+            for &(x, direction) in crossings.iter() {
+                let before = winding;
+                winding += direction;
+                if before == 0 && winding != 0 {
+                    span_start = x;
+                } else if before != 0 && winding == 0 {
+                    // Pixels whose centers are inside the span
+                    let left = math::ceil(span_start - 0.5).max(clip.left());
+                    let right = math::ceil(x - 0.5).min(clip.right());
+                    if right > left {
+                        span(left, y, (right - left) as u32);
+                    }
+                }
+            }
+        }
+    }
 }
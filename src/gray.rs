@@ -0,0 +1,302 @@
+// SPDX-License-Identifier: MIT
+
+//! Images with one 8-bit channel, used as grayscale or as an alpha mask (A8), taking a
+//! quarter of the memory of an [`Image`].
+
+#[cfg(not(feature = "std"))]
+use alloc::{boxed::Box, vec, vec::Vec};
+
+use core::cell::Cell;
+
+use crate::color::Color;
+use crate::image::Image;
+use crate::rect::Rect;
+use crate::renderer::Renderer;
+use crate::Mode;
+
+/// An image with one 8-bit value per pixel
+#[derive(Clone)]
+pub struct GrayImage {
+    w: u32,
+    h: u32,
+    data: Box<[u8]>,
+}
+
+impl GrayImage {
+    /// An image filled with zero, which is black or fully transparent
+    pub fn new(w: u32, h: u32) -> Self {
+        GrayImage {
+            w,
+            h,
+            data: vec![0; w as usize * h as usize].into_boxed_slice(),
+        }
+    }
+
+    /// `None` if the data is not `w * h` values
+    pub fn from_data(w: u32, h: u32, data: Box<[u8]>) -> Option<Self> {
+        if data.len() != w as usize * h as usize {
+            return None;
+        }
+        Some(GrayImage { w, h, data })
+    }
+
+    /// The luminance of an image, using the Rec. 601 weights and ignoring alpha
+    pub fn from_image_luminance(image: &Image) -> Self {
+        Self::from_image_with(image, luminance)
+    }
+
+    /// The alpha channel of an image, to use it as a mask
+    pub fn from_image_alpha(image: &Image) -> Self {
+        Self::from_image_with(image, |color| color.a())
+    }
+
+    fn from_image_with(image: &Image, channel: impl Fn(Color) -> u8) -> Self {
+        let data: Vec<u8> = image.data().iter().map(|&color| channel(color)).collect();
+        GrayImage {
+            w: image.width(),
+            h: image.height(),
+            data: data.into_boxed_slice(),
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.w
+    }
+
+    pub fn height(&self) -> u32 {
+        self.h
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn data_mut(&mut self) -> &mut [u8] {
+        &mut self.data
+    }
+
+    /// The value of a pixel, `None` outside of the image
+    pub fn get(&self, x: i32, y: i32) -> Option<u8> {
+        if x < 0 || y < 0 || x >= self.w as i32 || y >= self.h as i32 {
+            return None;
+        }
+        Some(self.data[y as usize * self.w as usize + x as usize])
+    }
+
+    pub fn set(&mut self, x: i32, y: i32, value: u8) {
+        if x >= 0 && y >= 0 && x < self.w as i32 && y < self.h as i32 {
+            self.data[y as usize * self.w as usize + x as usize] = value;
+        }
+    }
+
+    /// Fill a rectangle with one value
+    pub fn fill_rect(&mut self, rect: &Rect, value: u8) {
+        let rect = rect.intersection(&Rect::new(0, 0, self.w, self.h));
+        for y in rect.top()..rect.bottom() {
+            let start = y as usize * self.w as usize;
+            self.data[start + rect.left() as usize..start + rect.right() as usize].fill(value);
+        }
+    }
+
+    /// A renderer drawing into the image, with colors stored as their luminance. Drawing in
+    /// white over black leaves the coverage of each pixel, as for a mask.
+    pub fn renderer(&mut self) -> GrayRenderer<'_> {
+        let pixels = self
+            .data
+            .iter()
+            .map(|&value| Color::rgb(value, value, value))
+            .collect();
+        GrayRenderer {
+            image: self,
+            pixels,
+            mode: Cell::new(Mode::Blend),
+        }
+    }
+
+    /// An opaque grayscale image
+    pub fn to_image(&self) -> Image {
+        self.to_image_with(|value| Color::rgb(value, value, value))
+    }
+
+    /// An image of one color, with the values as its alpha
+    pub fn to_mask_image(&self, color: Color) -> Image {
+        self.to_image_with(|value| mask(color, value))
+    }
+
+    fn to_image_with(&self, color: impl Fn(u8) -> Color) -> Image {
+        let data: Vec<Color> = self.data.iter().map(|&value| color(value)).collect();
+        Image::from_data(self.w, self.h, data.into_boxed_slice())
+            .expect("gray image has w * h pixels")
+    }
+
+    /// Draw the whole image on a renderer as opaque gray
+    pub fn draw<R: Renderer + ?Sized>(&self, renderer: &mut R, x: i32, y: i32) {
+        self.draw_with(renderer, x, y, |value| Color::rgb(value, value, value));
+    }
+
+    /// Draw `color` on a renderer through the image used as an alpha mask, as for a cached glyph
+    pub fn draw_mask<R: Renderer + ?Sized>(&self, renderer: &mut R, x: i32, y: i32, color: Color) {
+        self.draw_with(renderer, x, y, |value| mask(color, value));
+    }
+
+    fn draw_with<R: Renderer + ?Sized>(
+        &self,
+        renderer: &mut R,
+        x: i32,
+        y: i32,
+        color: impl Fn(u8) -> Color,
+    ) {
+        let clip = Rect::new(x, y, self.w, self.h).intersection(&Rect::new(
+            0,
+            0,
+            renderer.width(),
+            renderer.height(),
+        ));
+        for dest_y in clip.top()..clip.bottom() {
+            let row = (dest_y - y) as usize * self.w as usize;
+            for dest_x in clip.left()..clip.right() {
+                let value = self.data[row + (dest_x - x) as usize];
+                renderer.pixel(dest_x, dest_y, color(value));
+            }
+        }
+    }
+}
+
+/// The luminance of a color, using the Rec. 601 weights and ignoring alpha
+fn luminance(color: Color) -> u8 {
+    ((color.r() as u32 * 299 + color.g() as u32 * 587 + color.b() as u32 * 114 + 500) / 1000) as u8
+}
+
+/// A renderer for a [`GrayImage`], drawing into colors that are converted back to the image on
+/// `sync`, `update_rects` and when dropped
+pub struct GrayRenderer<'a> {
+    image: &'a mut GrayImage,
+    pixels: Vec<Color>,
+    mode: Cell<Mode>,
+}
+
+impl GrayRenderer<'_> {
+    /// Convert a region of the colors into the image
+    fn store(&mut self, rect: Rect) {
+        let rect = rect.intersection(&Rect::new(0, 0, self.image.w, self.image.h));
+        let w = self.image.w as usize;
+        for y in rect.top() as usize..rect.bottom() as usize {
+            for x in rect.left() as usize..rect.right() as usize {
+                self.image.data[y * w + x] = luminance(self.pixels[y * w + x]);
+            }
+        }
+    }
+}
+
+impl Renderer for GrayRenderer<'_> {
+    fn width(&self) -> u32 {
+        self.image.w
+    }
+
+    fn height(&self) -> u32 {
+        self.image.h
+    }
+
+    fn data(&self) -> &[Color] {
+        &self.pixels
+    }
+
+    fn data_mut(&mut self) -> &mut [Color] {
+        &mut self.pixels
+    }
+
+    fn mode(&self) -> &Cell<Mode> {
+        &self.mode
+    }
+
+    fn sync(&mut self) -> bool {
+        self.store(Rect::new(0, 0, self.image.w, self.image.h));
+        true
+    }
+
+    fn update(&mut self) -> bool {
+        self.sync()
+    }
+
+    fn update_rects(&mut self, rects: &[(i32, i32, u32, u32)]) -> bool {
+        for &(x, y, w, h) in rects {
+            self.store(Rect::new(x, y, w, h));
+        }
+        true
+    }
+}
+
+impl Drop for GrayRenderer<'_> {
+    fn drop(&mut self) {
+        self.sync();
+    }
+}
+
+/// `color` with its alpha scaled by a mask value
+fn mask(color: Color, value: u8) -> Color {
+    let alpha = (color.a() as u32 * value as u32 + 127) / 255;
+    Color::rgba(color.r(), color.g(), color.b(), alpha as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WHITE: Color = Color::rgb(255, 255, 255);
+
+    #[test]
+    fn draw_rect() {
+        let mut image = GrayImage::new(8, 6);
+        {
+            let mut renderer = image.renderer();
+            renderer.rect(2, 1, 3, 4, WHITE);
+            renderer.rect(6, 0, 10, 2, Color::rgba(255, 255, 255, 128));
+        }
+        for y in 0..6 {
+            for x in 0..8 {
+                let expected = if (2..5).contains(&x) && (1..5).contains(&y) {
+                    255
+                } else if x >= 6 && y < 2 {
+                    128
+                } else {
+                    0
+                };
+                let value = image.get(x, y).unwrap();
+                assert!(value.abs_diff(expected) <= 1, "({}, {}) {}", x, y, value);
+            }
+        }
+    }
+
+    #[test]
+    fn draw_partial_coverage() {
+        let mut image = GrayImage::new(4, 1);
+        image.renderer().rect_f(0.5, 0.0, 2.0, 1.0, WHITE);
+        let coverage = image.data();
+        for (value, expected) in coverage.iter().zip([128, 255, 128, 0]) {
+            assert!(value.abs_diff(expected) <= 1, "{:?}", coverage);
+        }
+    }
+
+    #[cfg(any(feature = "unifont", feature = "unifont-subset"))]
+    #[test]
+    fn draw_char() {
+        let mut image = GrayImage::new(8, 16);
+        image.renderer().char(0, 0, 'A', WHITE);
+        let glyph = crate::font::glyph('A').unwrap();
+        for (y, row) in glyph.iter().enumerate() {
+            for x in 0..8 {
+                let expected = if row & (0x80 >> x) != 0 { 255 } else { 0 };
+                assert_eq!(image.get(x, y as i32), Some(expected), "({}, {})", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn untouched_values_are_kept() {
+        let data: Vec<u8> = (0..=255).collect();
+        let mut image = GrayImage::from_data(16, 16, data.clone().into_boxed_slice()).unwrap();
+        image.renderer().pixel(0, 0, Color::rgb(255, 0, 0));
+        assert_eq!(image.data()[0], 76);
+        assert_eq!(image.data()[1..], data[1..]);
+    }
+}
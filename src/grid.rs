@@ -0,0 +1,325 @@
+// SPDX-License-Identifier: MIT
+
+//! A fixed-cell text grid, as used by terminals and consoles.
+
+#[cfg(not(feature = "std"))]
+use alloc::{vec, vec::Vec};
+use core::cmp;
+
+use crate::color::Color;
+use crate::rect::Rect;
+use crate::renderer::Renderer;
+use crate::text::{self, TextStyle, Unifont};
+
+/// Damaged spans reported to `update_rects` beyond which the whole grid is reported instead
+const MAX_DAMAGE_RECTS: usize = 32;
+
+/// Display attributes of a grid cell
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CellAttributes {
+    /// Draw the character thicker
+    pub bold: bool,
+    /// Draw a line under the character
+    pub underline: bool,
+    /// Swap the foreground and background colors
+    pub inverse: bool,
+    /// Hide the character while the grid is in the off phase of blinking
+    pub blink: bool,
+}
+
+/// One character cell of a [`Grid`]
+#[derive(Clone, Copy, Debug)]
+pub struct GridCell {
+    /// The character drawn in the cell, a space for none
+    pub c: char,
+    /// Color of the character, and of its underline
+    pub fg: Color,
+    /// Color filling the rest of the cell
+    pub bg: Color,
+    /// Styles applied when drawing the cell
+    pub attributes: CellAttributes,
+}
+
+impl GridCell {
+    /// A cell without a character
+    pub const fn blank(fg: Color, bg: Color) -> Self {
+        GridCell {
+            c: ' ',
+            fg,
+            bg,
+            attributes: CellAttributes {
+                bold: false,
+                underline: false,
+                inverse: false,
+                blink: false,
+            },
+        }
+    }
+}
+
+/// What a cell looks like on screen after applying inverse and blinking
+#[derive(Clone, Copy)]
+struct Appearance {
+    c: char,
+    fg: Color,
+    bg: Color,
+    bold: bool,
+    underline: bool,
+}
+
+/// Compare including alpha, unlike `Color`
+impl PartialEq for Appearance {
+    fn eq(&self, other: &Appearance) -> bool {
+        self.c == other.c
+            && self.fg.data == other.fg.data
+            && self.bg.data == other.bg.data
+            && self.bold == other.bold
+            && self.underline == other.underline
+    }
+}
+
+/// A grid of character cells drawn on a renderer.
+/// Only cells that changed since the last [`Grid::draw`] are redrawn.
+pub struct Grid {
+    columns: u32,
+    rows: u32,
+    style: TextStyle,
+    cells: Vec<GridCell>,
+    drawn: Vec<Option<Appearance>>,
+    origin: (i32, i32),
+    blink_visible: bool,
+}
+
+impl Grid {
+    /// Create a grid of white on black blank cells, drawn with the unscaled font
+    pub fn new(columns: u32, rows: u32) -> Self {
+        Self::with_style(columns, rows, TextStyle::new())
+    }
+
+    /// Create a grid drawn with the given text style, only its scale and smoothing are used
+    pub fn with_style(columns: u32, rows: u32, style: TextStyle) -> Self {
+        let style = TextStyle {
+            scale: style.scale,
+            smooth: style.smooth,
+            ..TextStyle::new()
+        };
+        let len = columns as usize * rows as usize;
+        Grid {
+            columns,
+            rows,
+            style,
+            cells: vec![GridCell::blank(Color::rgb(255, 255, 255), Color::BLACK); len],
+            drawn: vec![None; len],
+            origin: (0, 0),
+            blink_visible: true,
+        }
+    }
+
+    pub fn columns(&self) -> u32 {
+        self.columns
+    }
+
+    pub fn rows(&self) -> u32 {
+        self.rows
+    }
+
+    /// Width of a cell in pixels
+    pub fn cell_width(&self) -> u32 {
+        self.style.advance()
+    }
+
+    /// Height of a cell in pixels
+    pub fn cell_height(&self) -> u32 {
+        self.style.line_height()
+    }
+
+    /// The area covered by the grid when drawn at (x, y)
+    pub fn bounds(&self, x: i32, y: i32) -> Rect {
+        Rect::new(
+            x,
+            y,
+            self.columns * self.cell_width(),
+            self.rows * self.cell_height(),
+        )
+    }
+
+    fn index(&self, column: u32, row: u32) -> Option<usize> {
+        if column < self.columns && row < self.rows {
+            Some(row as usize * self.columns as usize + column as usize)
+        } else {
+            None
+        }
+    }
+
+    /// Get a cell, `None` if out of the grid
+    pub fn get(&self, column: u32, row: u32) -> Option<&GridCell> {
+        self.index(column, row).map(|i| &self.cells[i])
+    }
+
+    /// Get a cell for modification, `None` if out of the grid
+    pub fn get_mut(&mut self, column: u32, row: u32) -> Option<&mut GridCell> {
+        self.index(column, row).map(move |i| &mut self.cells[i])
+    }
+
+    /// Replace a cell, ignored if out of the grid
+    pub fn set(&mut self, column: u32, row: u32, cell: GridCell) {
+        if let Some(i) = self.index(column, row) {
+            self.cells[i] = cell;
+        }
+    }
+
+    /// Replace every cell
+    pub fn fill(&mut self, cell: GridCell) {
+        for old in self.cells.iter_mut() {
+            *old = cell;
+        }
+    }
+
+    /// Move all rows up, filling the rows at the bottom with `blank`
+    pub fn scroll_up(&mut self, lines: u32, blank: GridCell) {
+        let lines = cmp::min(lines, self.rows) as usize * self.columns as usize;
+        self.cells.copy_within(lines.., 0);
+        let len = self.cells.len();
+        self.cells[len - lines..].fill(blank);
+    }
+
+    /// Move all rows down, filling the rows at the top with `blank`
+    pub fn scroll_down(&mut self, lines: u32, blank: GridCell) {
+        let lines = cmp::min(lines, self.rows) as usize * self.columns as usize;
+        let len = self.cells.len();
+        self.cells.copy_within(..len - lines, lines);
+        self.cells[..lines].fill(blank);
+    }
+
+    /// Change the number of cells, keeping the top left cells and filling new ones with `blank`
+    pub fn resize(&mut self, columns: u32, rows: u32, blank: GridCell) {
+        let mut cells = vec![blank; columns as usize * rows as usize];
+        for row in 0..cmp::min(rows, self.rows) {
+            for column in 0..cmp::min(columns, self.columns) {
+                cells[row as usize * columns as usize + column as usize] =
+                    self.cells[row as usize * self.columns as usize + column as usize];
+            }
+        }
+        self.columns = columns;
+        self.rows = rows;
+        self.cells = cells;
+        self.drawn = vec![None; self.cells.len()];
+    }
+
+    /// Set the blink phase, blinking cells are hidden while `visible` is false
+    pub fn set_blink_visible(&mut self, visible: bool) {
+        self.blink_visible = visible;
+    }
+
+    /// Forget what was drawn, so the next [`Grid::draw`] redraws every cell
+    pub fn invalidate(&mut self) {
+        for drawn in self.drawn.iter_mut() {
+            *drawn = None;
+        }
+    }
+
+    fn appearance(&self, cell: &GridCell) -> Appearance {
+        let (mut fg, bg) = if cell.attributes.inverse {
+            (cell.bg, cell.fg)
+        } else {
+            (cell.fg, cell.bg)
+        };
+        let mut c = cell.c;
+        if cell.attributes.blink && !self.blink_visible {
+            fg = bg;
+            c = ' ';
+        }
+        Appearance {
+            c,
+            fg,
+            bg,
+            bold: cell.attributes.bold,
+            underline: cell.attributes.underline && fg.data != bg.data,
+        }
+    }
+
+    fn draw_cell<R: Renderer + ?Sized>(
+        &self,
+        renderer: &mut R,
+        x: i32,
+        y: i32,
+        appearance: &Appearance,
+    ) {
+        let (w, h) = (self.cell_width(), self.cell_height());
+        renderer.rect(x, y, w, h, appearance.bg);
+
+        let style = TextStyle {
+            bold: appearance.bold,
+            underline: appearance.underline,
+            ..self.style
+        };
+        let mut buf = [0; 4];
+        if let Some(cluster) =
+            text::rasterize_cluster(&Unifont, appearance.c.encode_utf8(&mut buf), &style)
+        {
+            cluster.mask.crop_width(w).draw(
+                renderer,
+                x + cluster.left,
+                y + cluster.top,
+                appearance.fg,
+            );
+        }
+    }
+
+    /// Draw the cells that changed since the last call with the top left corner at (x, y),
+    /// then report the redrawn area with `update_rects`.
+    /// Returns the result of `update_rects`, or true if nothing had to be redrawn.
+    pub fn draw<R: Renderer + ?Sized>(&mut self, renderer: &mut R, x: i32, y: i32) -> bool {
+        if self.origin != (x, y) {
+            self.origin = (x, y);
+            self.invalidate();
+        }
+
+        let (w, h) = (self.cell_width(), self.cell_height());
+        let mut damage: Vec<(i32, i32, u32, u32)> = Vec::new();
+        for row in 0..self.rows {
+            let cell_y = y + (row * h) as i32;
+            let mut span: Option<(u32, u32)> = None;
+            for column in 0..self.columns {
+                let i = row as usize * self.columns as usize + column as usize;
+                let appearance = self.appearance(&self.cells[i]);
+                if self.drawn[i] == Some(appearance) {
+                    continue;
+                }
+
+                self.draw_cell(renderer, x + (column * w) as i32, cell_y, &appearance);
+                self.drawn[i] = Some(appearance);
+                span = match span {
+                    Some((start, end)) if end == column => Some((start, column + 1)),
+                    Some((start, end)) => {
+                        damage.push((x + (start * w) as i32, cell_y, (end - start) * w, h));
+                        Some((column, column + 1))
+                    }
+                    None => Some((column, column + 1)),
+                };
+            }
+            if let Some((start, end)) = span {
+                damage.push((x + (start * w) as i32, cell_y, (end - start) * w, h));
+            }
+        }
+
+        if damage.is_empty() {
+            return true;
+        }
+        if damage.len() > MAX_DAMAGE_RECTS {
+            let bounds = damage
+                .iter()
+                .fold(Rect::default(), |bounds, &(x, y, w, h)| {
+                    let rect = Rect::new(x, y, w, h);
+                    if bounds.is_empty() {
+                        rect
+                    } else {
+                        bounds.container(&rect)
+                    }
+                });
+            damage.clear();
+            damage.push((bounds.left(), bounds.top(), bounds.width(), bounds.height()));
+        }
+        renderer.update_rects(&damage)
+    }
+}
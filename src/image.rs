@@ -1,12 +1,21 @@
+use crate::nine_slice::Insets;
 use crate::rect::Rect;
+use crate::resample::{self, Filter};
+use crate::simd;
+#[cfg(feature = "std")]
+use crate::transform::Affine;
 use crate::{Color, Mode, Renderer};
 use core::cell::Cell;
 use core::fmt::Display;
 use core::num::NonZero;
 use core::{cmp, mem, ptr};
 
+#[cfg(feature = "image")]
+pub use crate::metadata::Orientation;
+#[cfg(feature = "image")]
+use crate::metadata::{Exif, ImageMetadata};
 #[cfg(not(feature = "std"))]
-use alloc::{boxed::Box, vec};
+use alloc::{boxed::Box, vec, vec::Vec};
 #[cfg(feature = "image")]
 pub use fast_image_resize::FilterType as ResizeType;
 #[cfg(feature = "image")]
@@ -89,13 +98,13 @@ pub struct ImageRoi<'a> {
 }
 
 impl<'a> ImageRoi<'a> {
-    pub fn from_renderer(renderer: &'a impl Renderer, rect: &Rect) -> Self {
+    pub fn from_renderer(renderer: &'a (impl Renderer + ?Sized), rect: &Rect) -> Self {
         ImageRoi {
             width: rect.width() as usize,
             height: rect.height() as usize,
             left: rect.left() as usize,
             top: rect.top() as usize,
-            stride: renderer.width() as usize,
+            stride: renderer.stride(),
             data: renderer.data(),
         }
     }
@@ -116,33 +125,41 @@ impl<'a> ImageRoi<'a> {
         self.rows().flatten()
     }
 
+    pub fn width(&self) -> u32 {
+        self.width as u32
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height as u32
+    }
+
+    /// Pixel at (x, y) of the ROI, using the nearest edge pixel outside of it
+    pub(crate) fn get_clamped(&self, x: i32, y: i32) -> Color {
+        let x = x.clamp(0, self.width as i32 - 1) as usize;
+        let y = y.clamp(0, self.height as i32 - 1) as usize;
+        self.data
+            .get((self.top + y) * self.stride + self.left + x)
+            .copied()
+            .unwrap_or(Color::rgba(0, 0, 0, 0))
+    }
+
+    /// Scale the ROI to a new image of `w` by `h` pixels, without the `image` feature
+    pub fn resample(&self, w: u32, h: u32, filter: Filter) -> Image {
+        resample::resample(self, w, h, filter)
+    }
+
     /// Draw the ROI image on a renderer
-    pub fn draw<R: Renderer>(&self, renderer: &mut R, x: i32, mut y: i32) {
-        let mut offset = self.top * self.stride + self.left;
-        let last_offset = cmp::min(
-            (self.top + self.height) * self.stride + self.left,
-            self.data.len(),
+    pub fn draw<R: Renderer + ?Sized>(&self, renderer: &mut R, x: i32, y: i32) {
+        let offset = self.top * self.stride + self.left;
+        let data = self.data.get(offset..).unwrap_or(&[]);
+        renderer.image_stride(
+            x,
+            y,
+            self.width as u32,
+            self.height as u32,
+            self.stride,
+            data,
         );
-        if renderer.width() as usize == self.stride
-            && self.left == 0
-            && x == 0
-            && self.width == self.stride
-        {
-            renderer.image(
-                x,
-                y,
-                renderer.width(),
-                ((last_offset - offset) / self.stride) as u32,
-                &self.data[offset..],
-            );
-            return;
-        }
-        while offset < last_offset {
-            let next_offset = offset + self.stride;
-            renderer.image(x, y, self.width as u32, 1, &self.data[offset..]);
-            offset = next_offset;
-            y += 1;
-        }
     }
 }
 
@@ -174,13 +191,13 @@ pub struct ImageRoiMut<'a> {
 }
 
 impl<'a> ImageRoiMut<'a> {
-    pub fn from_renderer(renderer: &'a mut impl Renderer, rect: &Rect) -> Self {
+    pub fn from_renderer(renderer: &'a mut (impl Renderer + ?Sized), rect: &Rect) -> Self {
         ImageRoiMut {
             width: rect.width() as usize,
             height: rect.height() as usize,
             left: rect.left() as usize,
             top: rect.top() as usize,
-            stride: renderer.width() as usize,
+            stride: renderer.stride(),
             data: renderer.data_mut(),
         }
     }
@@ -212,19 +229,7 @@ impl<'a> ImageRoiMut<'a> {
     /// Draw another image on top with alpha blending.
     pub fn blend(&'a mut self, other: &ImageRoi) {
         for (self_row, other_row) in self.rows_mut().zip(other.rows()) {
-            for (old, new) in self_row.iter_mut().zip(other_row.iter()) {
-                let new_data = new.data;
-                let old_data = old.data;
-                let alpha = (new_data >> 24) & 0xFF;
-                let n_alpha = 255 - alpha;
-
-                let rb =
-                    ((n_alpha * (old_data & 0x00FF00FF)) + (alpha * (new_data & 0x00FF00FF))) >> 8;
-                let ag = (n_alpha * ((old_data & 0xFF00FF00) >> 8))
-                    + (alpha * (0x01000000 | ((new_data & 0x0000FF00) >> 8)));
-
-                old.data = (rb & 0x00FF00FF) | (ag & 0xFF00FF00);
-            }
+            simd::blend(self_row, other_row);
         }
     }
 
@@ -306,6 +311,7 @@ impl<'a> From<ImageRoiMut<'a>> for ImageRoi<'a> {
 pub struct ImageRef<'a> {
     w: u32,
     h: u32,
+    stride: usize,
     data: &'a mut [Color],
     mode: Cell<Mode>,
 }
@@ -315,16 +321,42 @@ impl<'a> ImageRef<'a> {
         ImageRef {
             w,
             h,
+            stride: w as usize,
             data,
             mode: Cell::new(Mode::Blend),
         }
     }
 
-    pub fn from_renderer(renderer: &'a mut impl Renderer) -> Self {
+    /// Borrow `h` rows of `w` pixels, each row starting `stride` pixels after the previous one,
+    /// such as a framebuffer with padded rows. Returns `None` if rows overlap or `data` is too
+    /// small.
+    pub fn from_data_with_stride(
+        w: u32,
+        h: u32,
+        stride: usize,
+        data: &'a mut [Color],
+    ) -> Option<Self> {
+        if stride < w as usize {
+            return None;
+        }
+        if h > 0 && data.len() < (h as usize - 1).checked_mul(stride)?.checked_add(w as usize)? {
+            return None;
+        }
+        Some(ImageRef {
+            w,
+            h,
+            stride,
+            data,
+            mode: Cell::new(Mode::Blend),
+        })
+    }
+
+    pub fn from_renderer(renderer: &'a mut (impl Renderer + ?Sized)) -> Self {
         let mode = renderer.mode().clone();
         ImageRef {
             w: renderer.width(),
             h: renderer.height(),
+            stride: renderer.stride(),
             data: renderer.data_mut(),
             mode,
         }
@@ -337,7 +369,7 @@ impl<'a> ImageRef<'a> {
             height: rect.height() as usize,
             left: rect.left() as usize,
             top: rect.top() as usize,
-            stride: self.w as usize,
+            stride: self.stride,
             data: self.data,
         }
     }
@@ -349,7 +381,7 @@ impl<'a> ImageRef<'a> {
             height: rect.height() as usize,
             left: rect.left() as usize,
             top: rect.top() as usize,
-            stride: self.w as usize,
+            stride: self.stride,
             data: &mut self.data,
         }
     }
@@ -361,14 +393,14 @@ impl<'a> ImageRef<'a> {
             height: rect.height() as usize,
             left: rect.left() as usize,
             top: rect.top() as usize,
-            stride: self.w as usize,
+            stride: self.stride,
             data: self.data,
         }
     }
 
     /// Draw the whole image on a renderer.
-    pub fn draw<R: Renderer>(&self, renderer: &mut R, x: i32, y: i32) {
-        renderer.image(x, y, self.w, self.h, &self.data);
+    pub fn draw<R: Renderer + ?Sized>(&self, renderer: &mut R, x: i32, y: i32) {
+        renderer.image_stride(x, y, self.w, self.h, self.stride, self.data);
     }
 }
 
@@ -393,6 +425,11 @@ impl<'a> Renderer for ImageRef<'a> {
         self.data
     }
 
+    /// Pixels between the rows of the borrowed renderer or data
+    fn stride(&self) -> usize {
+        self.stride
+    }
+
     fn mode(&self) -> &Cell<Mode> {
         &self.mode
     }
@@ -466,6 +503,103 @@ impl Image {
         Self::from_dynamic_image(img)
     }
 
+    /// Load an image, rotating and flipping it as its EXIF orientation says
+    #[cfg(feature = "image")]
+    pub fn from_path_oriented<P: AsRef<Path>>(path: P) -> Result<Self, ImageError> {
+        let reader = image::ImageReader::open(path).map_err(ImageError::IoError)?;
+        Self::from_reader_oriented(reader)
+    }
+
+    /// Load an image in memory, guessing its format, rotating and flipping it as its EXIF
+    /// orientation says
+    #[cfg(feature = "image")]
+    pub fn from_memory_oriented(data: &[u8]) -> Result<Self, ImageError> {
+        Self::from_reader_oriented(image::ImageReader::new(std::io::Cursor::new(data)))
+    }
+
+    #[cfg(feature = "image")]
+    fn from_reader_oriented<R: std::io::BufRead + std::io::Seek>(
+        reader: image::ImageReader<R>,
+    ) -> Result<Self, ImageError> {
+        use image::ImageDecoder;
+
+        let reader = reader.with_guessed_format().map_err(ImageError::IoError)?;
+        let mut decoder = reader.into_decoder()?;
+        let orientation = decoder.orientation()?;
+        let image = Self::from_dynamic_image(image::DynamicImage::from_decoder(decoder))?;
+        Ok(image.apply_orientation(orientation))
+    }
+
+    /// Load an image scaled down to fit in `max_w` by `max_h`, keeping its aspect ratio and
+    /// applying its EXIF orientation. Images that already fit are not scaled up.
+    /// Photos with a large enough embedded EXIF thumbnail use it instead of decoding the full image.
+    #[cfg(feature = "image")]
+    pub fn thumbnail_from_path<P: AsRef<Path>>(
+        path: P,
+        max_w: u32,
+        max_h: u32,
+    ) -> Result<Self, ImageError> {
+        let data = std::fs::read(path).map_err(ImageError::IoError)?;
+        Self::thumbnail_from_memory(&data, max_w, max_h)
+    }
+
+    /// Load an image in memory scaled down to fit in `max_w` by `max_h`,
+    /// see [`Image::thumbnail_from_path`]
+    #[cfg(feature = "image")]
+    pub fn thumbnail_from_memory(data: &[u8], max_w: u32, max_h: u32) -> Result<Self, ImageError> {
+        let reader = image::ImageReader::new(std::io::Cursor::new(data));
+        let (metadata, exif) = ImageMetadata::from_reader(reader)?;
+        let (w, h) = metadata.oriented_size();
+        let (thumb_w, thumb_h) = if w <= max_w && h <= max_h {
+            (w, h)
+        } else if w as u64 * max_h as u64 > h as u64 * max_w as u64 {
+            (max_w, (h as u64 * max_w as u64 / w as u64).max(1) as u32)
+        } else {
+            ((w as u64 * max_h as u64 / h as u64).max(1) as u32, max_h)
+        };
+        if thumb_w == 0 || thumb_h == 0 {
+            return Ok(Self::empty());
+        }
+
+        // Embedded thumbnails can be letterboxed to another aspect ratio, which is not usable
+        let embedded = exif
+            .as_deref()
+            .and_then(Exif::new)
+            .and_then(|exif| exif.thumbnail())
+            .and_then(|data| Self::from_memory_with_format(data, ImageFormat::Jpeg).ok())
+            .map(|image| image.apply_orientation(metadata.orientation))
+            .filter(|image| {
+                let (a, b) = (image.w as u64 * h as u64, image.h as u64 * w as u64);
+                image.w >= thumb_w && image.h >= thumb_h && a.abs_diff(b) * 100 <= a
+            });
+        let image = match embedded {
+            Some(image) => image,
+            None => Self::from_memory_with_format(data, metadata.format)?
+                .apply_orientation(metadata.orientation),
+        };
+
+        if image.w == thumb_w && image.h == thumb_h {
+            Ok(image)
+        } else {
+            Ok(image.resize(thumb_w, thumb_h, ResizeType::Bilinear))
+        }
+    }
+
+    /// Rotate and flip the image for display, as described by an orientation
+    #[cfg(feature = "image")]
+    pub fn apply_orientation(self, orientation: Orientation) -> Self {
+        match orientation {
+            Orientation::NoTransforms => self,
+            Orientation::Rotate90 => self.rotate90(),
+            Orientation::Rotate180 => self.rotate180(),
+            Orientation::Rotate270 => self.rotate270(),
+            Orientation::FlipHorizontal => self.flip_horizontal(),
+            Orientation::FlipVertical => self.flip_vertical(),
+            Orientation::Rotate90FlipH => self.rotate90().flip_horizontal(),
+            Orientation::Rotate270FlipH => self.rotate270().flip_horizontal(),
+        }
+    }
+
     #[cfg(feature = "image")]
     fn from_dynamic_image(
         d_img: image::ImageResult<image::DynamicImage>,
@@ -484,6 +618,40 @@ impl Image {
         )
     }
 
+    #[cfg(feature = "image")]
+    fn to_rgba_image(&self) -> image::RgbaImage {
+        let data = self
+            .data
+            .iter()
+            .flat_map(|c| [c.r(), c.g(), c.b(), c.a()])
+            .collect();
+        image::RgbaImage::from_raw(self.w, self.h, data).unwrap()
+    }
+
+    /// Encode the image to a file in memory.
+    /// Formats without an alpha channel, like JPEG, drop the alpha.
+    #[cfg(feature = "image")]
+    pub fn encode(&self, format: ImageFormat) -> Result<Vec<u8>, ImageError> {
+        let mut data = Vec::new();
+        let mut cursor = std::io::Cursor::new(&mut data);
+        let img = image::DynamicImage::ImageRgba8(self.to_rgba_image());
+        match format {
+            ImageFormat::Jpeg => {
+                image::DynamicImage::ImageRgb8(img.to_rgb8()).write_to(&mut cursor, format)?
+            }
+            _ => img.write_to(&mut cursor, format)?,
+        }
+        Ok(data)
+    }
+
+    /// Save the image to a file.
+    /// Formats without an alpha channel, like JPEG, drop the alpha.
+    #[cfg(feature = "image")]
+    pub fn save<P: AsRef<Path>>(&self, path: P, format: ImageFormat) -> Result<(), ImageError> {
+        let data = self.encode(format)?;
+        std::fs::write(path, data).map_err(ImageError::IoError)
+    }
+
     #[cfg(feature = "image")]
     pub fn resize(&self, w: u32, h: u32, resize_type: ResizeType) -> Self {
         use fast_image_resize::{images::Image, PixelType, ResizeAlg, ResizeOptions};
@@ -516,6 +684,11 @@ impl Image {
         Self::from_data_unchecked(w, h, dst_color)
     }
 
+    /// Scale the image to `w` by `h` pixels, without the `image` feature
+    pub fn resample(&self, w: u32, h: u32, filter: Filter) -> Self {
+        resample::resample(&self.roi(&Rect::new(0, 0, self.w, self.h)), w, h, filter)
+    }
+
     pub fn resize_exact(&self, scale: NonZero<u32>) -> Self {
         let scale = scale.get();
         if scale == 1 {
@@ -541,6 +714,116 @@ impl Image {
         Self::from_data_unchecked(self.w * scale, self.h * scale, new_data)
     }
 
+    /// Copy the part of the image inside `rect`, clipped to the image
+    pub fn crop(&self, rect: &Rect) -> Self {
+        let rect = rect.intersection(&Rect::new(0, 0, self.w, self.h));
+        let data: Vec<Color> = self.roi(&rect).cells().copied().collect();
+        Self::from_data_unchecked(rect.width(), rect.height(), data.into_boxed_slice())
+    }
+
+    /// Mirror the image left to right
+    pub fn flip_horizontal(&self) -> Self {
+        let mut image = self.clone();
+        image.flip_horizontal_in_place();
+        image
+    }
+
+    pub fn flip_horizontal_in_place(&mut self) {
+        if self.w > 0 {
+            for row in self.data.chunks_exact_mut(self.w as usize) {
+                row.reverse();
+            }
+        }
+    }
+
+    /// Mirror the image top to bottom
+    pub fn flip_vertical(&self) -> Self {
+        let mut image = self.clone();
+        image.flip_vertical_in_place();
+        image
+    }
+
+    pub fn flip_vertical_in_place(&mut self) {
+        let w = self.w as usize;
+        let h = self.h as usize;
+        for y in 0..h / 2 {
+            let (top, bottom) = self.data.split_at_mut((h - 1 - y) * w);
+            top[y * w..(y + 1) * w].swap_with_slice(&mut bottom[..w]);
+        }
+    }
+
+    /// Rotate the image a quarter turn clockwise
+    pub fn rotate90(&self) -> Self {
+        let (w, h) = (self.w as usize, self.h as usize);
+        let mut data = Vec::with_capacity(self.data.len());
+        for y in 0..w {
+            for x in 0..h {
+                data.push(self.data[(h - 1 - x) * w + y]);
+            }
+        }
+        Self::from_data_unchecked(self.h, self.w, data.into_boxed_slice())
+    }
+
+    /// Rotate the image half a turn
+    pub fn rotate180(&self) -> Self {
+        let mut image = self.clone();
+        image.rotate180_in_place();
+        image
+    }
+
+    pub fn rotate180_in_place(&mut self) {
+        self.data.reverse();
+    }
+
+    /// Rotate the image a quarter turn counterclockwise
+    pub fn rotate270(&self) -> Self {
+        let (w, h) = (self.w as usize, self.h as usize);
+        let mut data = Vec::with_capacity(self.data.len());
+        for y in 0..w {
+            for x in 0..h {
+                data.push(self.data[x * w + (w - 1 - y)]);
+            }
+        }
+        Self::from_data_unchecked(self.h, self.w, data.into_boxed_slice())
+    }
+
+    /// Rotate the image clockwise by an angle in radians around its center, into a new image
+    /// just large enough to hold it. Areas not covered by the image are filled with `fill`.
+    #[cfg(feature = "std")]
+    pub fn rotate(&self, radians: f32, fill: Color, filter: Filter) -> Self {
+        let rotation = Affine::rotate(radians);
+        let (w, h) = (self.w as f32, self.h as f32);
+        // Ignore tiny errors, so right angles do not grow the image
+        let size = |size: f32| (size - 1e-3).ceil().max(0.0) as u32;
+        let new_w = size((rotation.a * w).abs() + (rotation.c * h).abs());
+        let new_h = size((rotation.b * w).abs() + (rotation.d * h).abs());
+
+        let transform = Affine::translate(-w / 2.0, -h / 2.0)
+            .then(&rotation)
+            .then(&Affine::translate(new_w as f32 / 2.0, new_h as f32 / 2.0));
+        let mut image = Self::from_color(new_w, new_h, fill);
+        image.mode().set(Mode::Overwrite);
+        image.draw_image_transformed(
+            &self.roi(&Rect::new(0, 0, self.w, self.h)),
+            &transform,
+            filter,
+        );
+        image.mode().set(Mode::Blend);
+        image
+    }
+
+    /// Add borders of `color` around the image
+    pub fn pad(&self, insets: Insets, color: Color) -> Self {
+        let w = self.w + insets.left + insets.right;
+        let h = self.h + insets.top + insets.bottom;
+        let mut image = Self::from_color(w, h, color);
+        for (y, row) in self.data.chunks_exact(self.w.max(1) as usize).enumerate() {
+            let start = (insets.top as usize + y) * w as usize + insets.left as usize;
+            image.data[start..start + row.len()].copy_from_slice(row);
+        }
+        image
+    }
+
     pub fn width(&self) -> u32 {
         self.w
     }
@@ -578,7 +861,7 @@ impl Image {
     }
 
     /// Draw the whole image on a renderer.
-    pub fn draw<R: Renderer>(&self, renderer: &mut R, x: i32, y: i32) {
+    pub fn draw<R: Renderer + ?Sized>(&self, renderer: &mut R, x: i32, y: i32) {
         renderer.image(x, y, self.w, self.h, &self.data);
     }
 }
@@ -695,7 +978,7 @@ impl ImageAligned {
     }
 
     /// Draw the whole image on a renderer.
-    pub fn draw<R: Renderer>(&self, renderer: &mut R, x: i32, y: i32) {
+    pub fn draw<R: Renderer + ?Sized>(&self, renderer: &mut R, x: i32, y: i32) {
         renderer.image(x, y, self.w, self.h, &self.data);
     }
 }
@@ -734,3 +1017,41 @@ impl Renderer for ImageAligned {
         &self.mode
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn image_ref_with_stride() {
+        let (w, h, stride) = (5, 4, 8);
+        let mut data = [Color::rgba(0, 0, 0, 0); 3 * 8 + 5];
+        assert!(ImageRef::from_data_with_stride(w, h, 4, &mut data).is_none());
+        assert!(ImageRef::from_data_with_stride(w, h, stride, &mut data[..28]).is_none());
+
+        let mut image = ImageRef::from_data_with_stride(w, h, stride, &mut data).unwrap();
+        assert_eq!(image.stride(), stride);
+        image.set(Color::rgb(1, 2, 3));
+        image.rect(1, 1, 10, 10, Color::rgb(4, 5, 6));
+        assert_eq!(image.getpixel(0, 3), Color::rgb(1, 2, 3));
+        assert_eq!(image.getpixel(4, 3), Color::rgb(4, 5, 6));
+
+        for (y, row) in data.chunks(stride).enumerate() {
+            for (x, pixel) in row.iter().enumerate() {
+                let expected = if x >= w as usize {
+                    0
+                } else if x >= 1 && y >= 1 {
+                    Color::rgb(4, 5, 6).data
+                } else {
+                    Color::rgb(1, 2, 3).data
+                };
+                assert_eq!(pixel.data, expected, "({}, {})", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn image_ref_empty() {
+        assert!(ImageRef::from_data_with_stride(3, 0, 3, &mut []).is_some());
+    }
+}
@@ -0,0 +1,367 @@
+// SPDX-License-Identifier: MIT
+
+//! Images with 8-bit indices into a palette, which can be changed to recolor or cycle colors
+//! without touching the pixels.
+
+#[cfg(not(feature = "std"))]
+use alloc::{boxed::Box, vec, vec::Vec};
+
+use core::cell::Cell;
+use core::cmp;
+
+use crate::color::Color;
+use crate::image::Image;
+use crate::rect::Rect;
+use crate::renderer::Renderer;
+use crate::Mode;
+
+/// How colors between the palette entries are approximated when converting to a palette
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Dither {
+    /// The closest entry, giving flat bands in gradients
+    #[default]
+    None,
+    /// A fixed 8x8 Bayer threshold pattern, which is stable between animation frames
+    Ordered,
+    /// Floyd-Steinberg error diffusion, the most accurate but noisy when the image changes
+    FloydSteinberg,
+}
+
+const BAYER: [[u8; 8]; 8] = [
+    [0, 32, 8, 40, 2, 34, 10, 42],
+    [48, 16, 56, 24, 50, 18, 58, 26],
+    [12, 44, 4, 36, 14, 46, 6, 38],
+    [60, 28, 52, 20, 62, 30, 54, 22],
+    [3, 35, 11, 43, 1, 33, 9, 41],
+    [51, 19, 59, 27, 49, 17, 57, 25],
+    [15, 47, 7, 39, 13, 45, 5, 37],
+    [63, 31, 55, 23, 61, 29, 53, 21],
+];
+
+/// An image of indices into a palette of up to 256 colors. Indices past the end of the
+/// palette are transparent.
+#[derive(Clone)]
+pub struct IndexedImage {
+    w: u32,
+    h: u32,
+    data: Box<[u8]>,
+    palette: Vec<Color>,
+}
+
+impl IndexedImage {
+    /// An image filled with index 0, `None` if the palette has more than 256 colors
+    pub fn new(w: u32, h: u32, palette: Vec<Color>) -> Option<Self> {
+        Self::from_data(
+            w,
+            h,
+            vec![0; w as usize * h as usize].into_boxed_slice(),
+            palette,
+        )
+    }
+
+    /// `None` if the data is not `w * h` indices or the palette has more than 256 colors
+    pub fn from_data(w: u32, h: u32, data: Box<[u8]>, palette: Vec<Color>) -> Option<Self> {
+        if data.len() != w as usize * h as usize || palette.len() > 256 {
+            return None;
+        }
+        Some(IndexedImage {
+            w,
+            h,
+            data,
+            palette,
+        })
+    }
+
+    /// Convert an image to the closest colors of a palette, `None` if the palette is empty or
+    /// has more than 256 colors
+    pub fn from_image(image: &Image, palette: Vec<Color>, dither: Dither) -> Option<Self> {
+        if palette.is_empty() {
+            return None;
+        }
+        let mut indexed = Self::new(image.width(), image.height(), palette)?;
+        let (w, src) = (image.width() as usize, image.data());
+        match dither {
+            Dither::None => {
+                for (index, &color) in indexed.data.iter_mut().zip(src) {
+                    *index = nearest(&indexed.palette, channels(color));
+                }
+            }
+            Dither::Ordered => {
+                // Spread the threshold over the distance between palette colors, as if they
+                // were evenly spaced on each channel
+                let mut levels = 1;
+                while levels * levels * levels < indexed.palette.len() as i32 {
+                    levels += 1;
+                }
+                let spread = 255 / cmp::max(levels - 1, 1);
+                for (i, (index, &color)) in indexed.data.iter_mut().zip(src).enumerate() {
+                    let threshold = BAYER[(i / w) % 8][(i % w) % 8] as i32;
+                    let offset = (threshold * 2 - 63) * spread / 128;
+                    let mut pixel = channels(color);
+                    for channel in pixel.iter_mut().take(3) {
+                        *channel += offset;
+                    }
+                    *index = nearest(&indexed.palette, pixel);
+                }
+            }
+            Dither::FloydSteinberg => {
+                // Errors carried to the current and next row, with a pixel of margin each side
+                let mut errors = vec![[0; 3]; w + 2];
+                let mut next = vec![[0; 3]; w + 2];
+                for (y, row) in indexed.data.chunks_exact_mut(w.max(1)).enumerate() {
+                    for (x, index) in row.iter_mut().enumerate() {
+                        let mut pixel = channels(src[y * w + x]);
+                        for c in 0..3 {
+                            pixel[c] = (pixel[c] + errors[x + 1][c] / 16).clamp(0, 255);
+                        }
+                        *index = nearest(&indexed.palette, pixel);
+                        let chosen = channels(indexed.palette[*index as usize]);
+                        for c in 0..3 {
+                            let error = pixel[c] - chosen[c];
+                            errors[x + 2][c] += error * 7;
+                            next[x][c] += error * 3;
+                            next[x + 1][c] += error * 5;
+                            next[x + 2][c] += error;
+                        }
+                    }
+                    errors.copy_from_slice(&next);
+                    next.iter_mut().for_each(|error| *error = [0; 3]);
+                }
+            }
+        }
+        Some(indexed)
+    }
+
+    pub fn width(&self) -> u32 {
+        self.w
+    }
+
+    pub fn height(&self) -> u32 {
+        self.h
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn data_mut(&mut self) -> &mut [u8] {
+        &mut self.data
+    }
+
+    pub fn palette(&self) -> &[Color] {
+        &self.palette
+    }
+
+    /// Change the palette, which recolors the whole image. It may grow up to 256 colors.
+    pub fn palette_mut(&mut self) -> &mut Vec<Color> {
+        &mut self.palette
+    }
+
+    /// Rotate the palette entries in `range` by `by` places, for palette cycling effects
+    pub fn cycle_palette(&mut self, range: core::ops::Range<usize>, by: isize) {
+        let end = cmp::min(range.end, self.palette.len());
+        let entries = match self.palette.get_mut(range.start..end) {
+            Some(entries) if !entries.is_empty() => entries,
+            _ => return,
+        };
+        let by = by.rem_euclid(entries.len() as isize) as usize;
+        entries.rotate_right(by);
+    }
+
+    /// The palette index of a pixel, `None` outside of the image
+    pub fn index(&self, x: i32, y: i32) -> Option<u8> {
+        if x < 0 || y < 0 || x >= self.w as i32 || y >= self.h as i32 {
+            return None;
+        }
+        Some(self.data[y as usize * self.w as usize + x as usize])
+    }
+
+    pub fn set_index(&mut self, x: i32, y: i32, index: u8) {
+        if x >= 0 && y >= 0 && x < self.w as i32 && y < self.h as i32 {
+            self.data[y as usize * self.w as usize + x as usize] = index;
+        }
+    }
+
+    /// Fill a rectangle with one palette index
+    pub fn fill_rect(&mut self, rect: &Rect, index: u8) {
+        let rect = rect.intersection(&Rect::new(0, 0, self.w, self.h));
+        for y in rect.top()..rect.bottom() {
+            let start = y as usize * self.w as usize;
+            self.data[start + rect.left() as usize..start + rect.right() as usize].fill(index);
+        }
+    }
+
+    /// The color of a palette index, transparent past the end of the palette
+    pub fn color(&self, index: u8) -> Color {
+        self.palette
+            .get(index as usize)
+            .copied()
+            .unwrap_or(Color::rgba(0, 0, 0, 0))
+    }
+
+    /// A renderer drawing into the image. Pixels whose color changes are set to the index of
+    /// the closest palette color, the others keep their index.
+    pub fn renderer(&mut self) -> IndexedRenderer<'_> {
+        let pixels = self.data.iter().map(|&index| self.color(index)).collect();
+        IndexedRenderer {
+            image: self,
+            pixels,
+            mode: Cell::new(Mode::Blend),
+        }
+    }
+
+    pub fn to_image(&self) -> Image {
+        let data: Vec<Color> = self.data.iter().map(|&index| self.color(index)).collect();
+        Image::from_data(self.w, self.h, data.into_boxed_slice())
+            .expect("indexed image has w * h pixels")
+    }
+
+    /// Draw the whole image on a renderer, looking up each pixel in the palette
+    pub fn draw<R: Renderer + ?Sized>(&self, renderer: &mut R, x: i32, y: i32) {
+        let clip = Rect::new(x, y, self.w, self.h).intersection(&Rect::new(
+            0,
+            0,
+            renderer.width(),
+            renderer.height(),
+        ));
+        for dest_y in clip.top()..clip.bottom() {
+            let row = (dest_y - y) as usize * self.w as usize;
+            for dest_x in clip.left()..clip.right() {
+                let index = self.data[row + (dest_x - x) as usize];
+                renderer.pixel(dest_x, dest_y, self.color(index));
+            }
+        }
+    }
+}
+
+/// A renderer for an [`IndexedImage`], drawing into colors that are converted back to the image
+/// on `sync`, `update_rects` and when dropped
+pub struct IndexedRenderer<'a> {
+    image: &'a mut IndexedImage,
+    pixels: Vec<Color>,
+    mode: Cell<Mode>,
+}
+
+impl IndexedRenderer<'_> {
+    /// Convert a region of the colors into the image
+    fn store(&mut self, rect: Rect) {
+        let rect = rect.intersection(&Rect::new(0, 0, self.image.w, self.image.h));
+        let w = self.image.w as usize;
+        for y in rect.top() as usize..rect.bottom() as usize {
+            for x in rect.left() as usize..rect.right() as usize {
+                let (i, color) = (y * w + x, self.pixels[y * w + x]);
+                // Compared with alpha, which `Color` equality ignores
+                if color.data != self.image.color(self.image.data[i]).data
+                    && !self.image.palette.is_empty()
+                {
+                    self.image.data[i] = nearest(&self.image.palette, channels(color));
+                }
+            }
+        }
+    }
+}
+
+impl Renderer for IndexedRenderer<'_> {
+    fn width(&self) -> u32 {
+        self.image.w
+    }
+
+    fn height(&self) -> u32 {
+        self.image.h
+    }
+
+    fn data(&self) -> &[Color] {
+        &self.pixels
+    }
+
+    fn data_mut(&mut self) -> &mut [Color] {
+        &mut self.pixels
+    }
+
+    fn mode(&self) -> &Cell<Mode> {
+        &self.mode
+    }
+
+    fn sync(&mut self) -> bool {
+        self.store(Rect::new(0, 0, self.image.w, self.image.h));
+        true
+    }
+
+    fn update(&mut self) -> bool {
+        self.sync()
+    }
+
+    fn update_rects(&mut self, rects: &[(i32, i32, u32, u32)]) -> bool {
+        for &(x, y, w, h) in rects {
+            self.store(Rect::new(x, y, w, h));
+        }
+        true
+    }
+}
+
+impl Drop for IndexedRenderer<'_> {
+    fn drop(&mut self) {
+        self.sync();
+    }
+}
+
+fn channels(color: Color) -> [i32; 4] {
+    [
+        color.r() as i32,
+        color.g() as i32,
+        color.b() as i32,
+        color.a() as i32,
+    ]
+}
+
+/// Index of the palette color closest to `pixel`
+fn nearest(palette: &[Color], pixel: [i32; 4]) -> u8 {
+    let mut best = (0, i32::MAX);
+    for (i, &color) in palette.iter().enumerate() {
+        let entry = channels(color);
+        let distance = (0..4)
+            .map(|c| (pixel[c] - entry[c]) * (pixel[c] - entry[c]))
+            .sum();
+        if distance < best.1 {
+            best = (i, distance);
+        }
+    }
+    best.0 as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn draw_rect() {
+        let palette = vec![
+            Color::rgb(0, 0, 0),
+            Color::rgb(255, 0, 0),
+            Color::rgb(255, 255, 255),
+            // Duplicate of the first entry, which undrawn pixels keep
+            Color::rgb(0, 0, 0),
+        ];
+        let mut image = IndexedImage::new(6, 4, palette).unwrap();
+        image.fill_rect(&Rect::new(0, 3, 6, 1), 3);
+        {
+            let mut renderer = image.renderer();
+            renderer.rect(1, 1, 2, 2, Color::rgb(250, 10, 0));
+            renderer.pixel(5, 0, Color::rgb(240, 240, 240));
+        }
+        for y in 0..4 {
+            for x in 0..6 {
+                let expected = if (1..3).contains(&x) && (1..3).contains(&y) {
+                    1
+                } else if (x, y) == (5, 0) {
+                    2
+                } else if y == 3 {
+                    3
+                } else {
+                    0
+                };
+                assert_eq!(image.index(x, y), Some(expected), "({}, {})", x, y);
+            }
+        }
+    }
+}
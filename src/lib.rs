@@ -26,14 +26,41 @@ pub use color::Color;
 pub use event::*;
 pub use graphicspath::GraphicsPath;
 pub use renderer::Renderer;
+#[cfg(any(feature = "unifont", feature = "unifont-subset"))]
+pub use text::TextStyle;
 
+pub mod animation;
+mod blit;
 #[cfg(feature = "std")]
 mod blur;
+pub mod codec;
 pub mod color;
+pub mod damage;
 pub mod event;
 mod flags;
+pub mod flood;
+#[cfg(any(feature = "unifont", feature = "unifont-subset"))]
+pub mod font;
+pub mod framebuffer;
 pub mod graphicspath;
+pub mod gray;
+#[cfg(any(feature = "unifont", feature = "unifont-subset"))]
+pub mod grid;
 pub mod image;
+pub mod indexed;
+mod math;
+#[cfg(feature = "image")]
+pub mod metadata;
+pub mod nine_slice;
+mod parallel;
+pub mod pattern;
 pub mod rect;
 pub mod renderer;
+pub mod resample;
+mod simd;
+pub mod subpixel;
+#[cfg(any(feature = "unifont", feature = "unifont-subset"))]
+pub mod text;
+pub mod transform;
+pub mod triangle;
 pub use flags::*;
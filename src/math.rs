@@ -0,0 +1,28 @@
+// SPDX-License-Identifier: MIT
+
+// Float rounding that works without `std`, which is where `f32::floor` and friends live.
+
+#[inline(always)]
+pub(crate) fn floor(x: f32) -> i32 {
+    let t = x as i32;
+    if (t as f32) > x {
+        t - 1
+    } else {
+        t
+    }
+}
+
+#[inline(always)]
+pub(crate) fn ceil(x: f32) -> i32 {
+    let t = x as i32;
+    if (t as f32) < x {
+        t + 1
+    } else {
+        t
+    }
+}
+
+#[inline(always)]
+pub(crate) fn round(x: f32) -> i32 {
+    floor(x + 0.5)
+}
@@ -0,0 +1,272 @@
+// SPDX-License-Identifier: MIT
+
+//! Image file metadata that can be read without decoding the pixels.
+
+use std::io::{BufRead, Cursor, Seek};
+use std::path::Path;
+
+pub use image::metadata::Orientation;
+use image::{ImageDecoder, ImageError, ImageFormat, ImageReader};
+
+const TAG_X_RESOLUTION: u16 = 0x011A;
+const TAG_Y_RESOLUTION: u16 = 0x011B;
+const TAG_RESOLUTION_UNIT: u16 = 0x0128;
+const UNIT_INCH: u16 = 2;
+const UNIT_CENTIMETER: u16 = 3;
+const TAG_THUMBNAIL_OFFSET: u16 = 0x0201;
+const TAG_THUMBNAIL_LENGTH: u16 = 0x0202;
+
+/// Basic information about an image file, read from its header
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ImageMetadata {
+    pub format: ImageFormat,
+    /// Size of the stored image, before applying the orientation
+    pub width: u32,
+    pub height: u32,
+    /// How the image should be rotated or flipped for display, from EXIF
+    pub orientation: Orientation,
+    /// Horizontal and vertical dots per inch, from EXIF
+    pub dpi: Option<(f32, f32)>,
+    /// Whether an ICC color profile is embedded
+    pub has_icc_profile: bool,
+}
+
+impl ImageMetadata {
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, ImageError> {
+        Self::from_reader(ImageReader::open(path).map_err(ImageError::IoError)?).map(|(m, _)| m)
+    }
+
+    pub fn from_memory(data: &[u8]) -> Result<Self, ImageError> {
+        Self::from_reader(ImageReader::new(Cursor::new(data))).map(|(m, _)| m)
+    }
+
+    /// Read the metadata, also returning the EXIF chunk
+    pub(crate) fn from_reader<R: BufRead + Seek>(
+        reader: ImageReader<R>,
+    ) -> Result<(Self, Option<Vec<u8>>), ImageError> {
+        let reader = reader.with_guessed_format().map_err(ImageError::IoError)?;
+        let format = reader.format();
+        let mut decoder = reader.into_decoder()?;
+        let (width, height) = decoder.dimensions();
+        let orientation = decoder.orientation()?;
+        let exif = decoder.exif_metadata()?;
+        let dpi = exif
+            .as_deref()
+            .and_then(Exif::new)
+            .and_then(|exif| exif.dpi());
+        let has_icc_profile = decoder.icc_profile()?.is_some();
+        let metadata = ImageMetadata {
+            // The decoder could not have been created without a format
+            format: format.expect("image format is known"),
+            width,
+            height,
+            orientation,
+            dpi,
+            has_icc_profile,
+        };
+        Ok((metadata, exif))
+    }
+
+    /// Size of the image once the orientation is applied
+    pub fn oriented_size(&self) -> (u32, u32) {
+        match self.orientation {
+            Orientation::Rotate90
+            | Orientation::Rotate270
+            | Orientation::Rotate90FlipH
+            | Orientation::Rotate270FlipH => (self.height, self.width),
+            _ => (self.width, self.height),
+        }
+    }
+}
+
+/// An EXIF chunk, which is a TIFF file without image data
+pub(crate) struct Exif<'a> {
+    data: &'a [u8],
+    big_endian: bool,
+}
+
+impl<'a> Exif<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Option<Self> {
+        let big_endian = match data.get(..4)? {
+            [b'I', b'I', 42, 0] => false,
+            [b'M', b'M', 0, 42] => true,
+            _ => return None,
+        };
+        Some(Exif { data, big_endian })
+    }
+
+    fn u16(&self, offset: usize) -> Option<u16> {
+        let bytes = [*self.data.get(offset)?, *self.data.get(offset.checked_add(1)?)?];
+        Some(if self.big_endian {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        })
+    }
+
+    fn u32(&self, offset: usize) -> Option<u32> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.data.get(offset..offset.checked_add(4)?)?);
+        Some(if self.big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        })
+    }
+
+    /// A rational value stored at the offset found at `offset`
+    fn rational(&self, offset: usize) -> Option<f32> {
+        let offset = self.u32(offset)? as usize;
+        let denominator = self.u32(offset.checked_add(4)?)?;
+        if denominator == 0 {
+            return None;
+        }
+        Some(self.u32(offset)? as f32 / denominator as f32)
+    }
+
+    /// Offset of the first image directory, describing the main image
+    fn first_directory(&self) -> Option<usize> {
+        self.u32(4).map(|offset| offset as usize)
+    }
+
+    /// Offset of the directory after the one at `directory`
+    fn next_directory(&self, directory: usize) -> Option<usize> {
+        let entries = self.u16(directory)? as usize;
+        match self.u32(directory.checked_add(2 + entries * 12)?)? {
+            0 => None,
+            offset => Some(offset as usize),
+        }
+    }
+
+    /// Offset of the value of a tag in a directory
+    fn find(&self, directory: usize, tag: u16) -> Option<usize> {
+        (0..self.u16(directory)? as usize)
+            .map_while(|i| directory.checked_add(2 + i * 12))
+            .find(|&entry| self.u16(entry) == Some(tag))
+            .and_then(|entry| entry.checked_add(8))
+    }
+
+    /// Horizontal and vertical dots per inch of the main image
+    fn dpi(&self) -> Option<(f32, f32)> {
+        let directory = self.first_directory()?;
+        let unit = match self.find(directory, TAG_RESOLUTION_UNIT) {
+            Some(value) => self.u16(value)?,
+            None => UNIT_INCH,
+        };
+        let scale = match unit {
+            UNIT_INCH => 1.0,
+            UNIT_CENTIMETER => 2.54,
+            _ => return None,
+        };
+        let x = self.rational(self.find(directory, TAG_X_RESOLUTION)?)? * scale;
+        let y = self.rational(self.find(directory, TAG_Y_RESOLUTION)?)? * scale;
+        if x > 0.0 && y > 0.0 {
+            Some((x, y))
+        } else {
+            None
+        }
+    }
+
+    /// The embedded JPEG thumbnail, described by the second image directory
+    pub(crate) fn thumbnail(&self) -> Option<&'a [u8]> {
+        let directory = self.next_directory(self.first_directory()?)?;
+        let offset = self.u32(self.find(directory, TAG_THUMBNAIL_OFFSET)?)? as usize;
+        let len = self.u32(self.find(directory, TAG_THUMBNAIL_LENGTH)?)? as usize;
+        self.data.get(offset..offset.checked_add(len)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A little endian EXIF chunk with a DPI of 300 by 150 and a 4-byte thumbnail
+    fn exif() -> Vec<u8> {
+        fn entry(data: &mut Vec<u8>, tag: u16, kind: u16, value: u32) {
+            data.extend_from_slice(&tag.to_le_bytes());
+            data.extend_from_slice(&kind.to_le_bytes());
+            data.extend_from_slice(&1u32.to_le_bytes());
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+
+        let mut data = b"II\x2A\x00".to_vec();
+        data.extend_from_slice(&8u32.to_le_bytes());
+        // First directory at 8, with three entries ending at 8 + 2 + 36 + 4 = 50
+        data.extend_from_slice(&3u16.to_le_bytes());
+        entry(&mut data, TAG_X_RESOLUTION, 5, 50);
+        entry(&mut data, TAG_Y_RESOLUTION, 5, 58);
+        entry(&mut data, TAG_RESOLUTION_UNIT, 3, UNIT_INCH as u32);
+        data.extend_from_slice(&66u32.to_le_bytes());
+        for value in [300u32, 1, 300, 2] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        // Second directory at 66, with two entries ending at 66 + 2 + 24 + 4 = 96
+        data.extend_from_slice(&2u16.to_le_bytes());
+        entry(&mut data, TAG_THUMBNAIL_OFFSET, 4, 96);
+        entry(&mut data, TAG_THUMBNAIL_LENGTH, 4, 4);
+        data.extend_from_slice(&0u32.to_le_bytes());
+        data.extend_from_slice(&[0xFF, 0xD8, 0xFF, 0xD9]);
+        data
+    }
+
+    #[test]
+    fn read() {
+        let data = exif();
+        let exif = Exif::new(&data).unwrap();
+        assert_eq!(exif.dpi(), Some((300.0, 150.0)));
+        assert_eq!(exif.thumbnail(), Some(&[0xFF, 0xD8, 0xFF, 0xD9][..]));
+    }
+
+    #[test]
+    fn truncated() {
+        let data = exif();
+        assert!(Exif::new(&data[..3]).is_none());
+        for len in 4..data.len() {
+            let exif = Exif::new(&data[..len]).unwrap();
+            if len < 66 {
+                assert_eq!(exif.dpi(), None, "{}", len);
+            }
+            assert_eq!(exif.thumbnail(), None, "{}", len);
+        }
+    }
+
+    #[test]
+    fn hostile_offsets() {
+        for offset in [u32::MAX, u32::MAX - 1, u32::MAX - 7] {
+            let bytes = offset.to_le_bytes();
+            let mut data = exif();
+
+            // First directory
+            data[4..8].copy_from_slice(&bytes);
+            let exif = Exif::new(&data).unwrap();
+            assert_eq!(exif.dpi(), None);
+            assert_eq!(exif.thumbnail(), None);
+
+            // Horizontal resolution
+            let mut data = self::exif();
+            data[18..22].copy_from_slice(&bytes);
+            let exif = Exif::new(&data).unwrap();
+            assert_eq!(exif.dpi(), None);
+            assert!(exif.thumbnail().is_some());
+
+            // Next directory and thumbnail
+            for at in [46, 76] {
+                let mut data = self::exif();
+                data[at..at + 4].copy_from_slice(&bytes);
+                let exif = Exif::new(&data).unwrap();
+                assert!(exif.dpi().is_some());
+                assert_eq!(exif.thumbnail(), None);
+            }
+        }
+
+        let data = exif();
+        let exif = Exif::new(&data).unwrap();
+        for offset in [usize::MAX, usize::MAX - 1, usize::MAX - 3] {
+            assert_eq!(exif.u16(offset), None);
+            assert_eq!(exif.u32(offset), None);
+            assert_eq!(exif.rational(offset), None);
+            assert_eq!(exif.next_directory(offset), None);
+            assert_eq!(exif.find(offset, TAG_X_RESOLUTION), None);
+        }
+    }
+}
@@ -0,0 +1,192 @@
+// SPDX-License-Identifier: MIT
+
+//! Nine-slice (nine-patch) images, for frames and buttons that can be drawn at any size.
+
+use core::cmp;
+
+use crate::image::Image;
+use crate::rect::Rect;
+use crate::renderer::Renderer;
+use crate::resample::Filter;
+
+/// Widths of the borders of an image, in pixels
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Insets {
+    pub left: u32,
+    pub top: u32,
+    pub right: u32,
+    pub bottom: u32,
+}
+
+impl Insets {
+    pub const fn new(left: u32, top: u32, right: u32, bottom: u32) -> Self {
+        Insets {
+            left,
+            top,
+            right,
+            bottom,
+        }
+    }
+
+    /// The same width on every side
+    pub const fn uniform(width: u32) -> Self {
+        Self::new(width, width, width, width)
+    }
+}
+
+/// How the edges and center of a nine-slice image fill their area
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SliceMode {
+    /// Scale the slice to the area
+    #[default]
+    Stretch,
+    /// Repeat the slice at its original size, cutting the last repetition
+    Tile,
+}
+
+/// An image split into four unscaled corners, four edges and a center by its border insets
+#[derive(Clone)]
+pub struct NineSlice {
+    image: Image,
+    insets: Insets,
+    /// How the edges are filled along their length
+    pub edges: SliceMode,
+    /// How the center is filled
+    pub center: SliceMode,
+    /// Sampling of stretched slices, and of the corners when the area is too small for them
+    pub filter: Filter,
+}
+
+impl NineSlice {
+    /// Split an image by its border insets, `None` if the insets do not fit in the image
+    pub fn new(image: Image, insets: Insets) -> Option<Self> {
+        if insets.left as u64 + insets.right as u64 > image.width() as u64
+            || insets.top as u64 + insets.bottom as u64 > image.height() as u64
+        {
+            return None;
+        }
+        Some(NineSlice {
+            image,
+            insets,
+            edges: SliceMode::Stretch,
+            center: SliceMode::Stretch,
+            filter: Filter::Nearest,
+        })
+    }
+
+    pub fn image(&self) -> &Image {
+        &self.image
+    }
+
+    pub fn insets(&self) -> Insets {
+        self.insets
+    }
+
+    /// The smallest size that shows the corners unscaled
+    pub fn min_size(&self) -> (u32, u32) {
+        (
+            self.insets.left + self.insets.right,
+            self.insets.top + self.insets.bottom,
+        )
+    }
+
+    /// Draw the image filling `rect`
+    pub fn draw<R: Renderer + ?Sized>(&self, renderer: &mut R, rect: Rect) {
+        let (w, h) = (self.image.width(), self.image.height());
+        let Insets {
+            left,
+            top,
+            right,
+            bottom,
+        } = self.insets;
+        let (dest_left, dest_right) = fit(left, right, rect.width());
+        let (dest_top, dest_bottom) = fit(top, bottom, rect.height());
+
+        let src_columns = [(0, left), (left, w - left - right), (w - right, right)];
+        let src_rows = [(0, top), (top, h - top - bottom), (h - bottom, bottom)];
+        let dest_columns = [
+            (rect.left(), dest_left),
+            (
+                rect.left() + dest_left as i32,
+                rect.width() - dest_left - dest_right,
+            ),
+            (rect.right() - dest_right as i32, dest_right),
+        ];
+        let dest_rows = [
+            (rect.top(), dest_top),
+            (
+                rect.top() + dest_top as i32,
+                rect.height() - dest_top - dest_bottom,
+            ),
+            (rect.bottom() - dest_bottom as i32, dest_bottom),
+        ];
+
+        for (row, &(src_y, src_h)) in src_rows.iter().enumerate() {
+            for (column, &(src_x, src_w)) in src_columns.iter().enumerate() {
+                let src = Rect::new(src_x as i32, src_y as i32, src_w, src_h);
+                let (dest_x, dest_w) = dest_columns[column];
+                let (dest_y, dest_h) = dest_rows[row];
+                let dest = Rect::new(dest_x, dest_y, dest_w, dest_h);
+                if src.is_empty() || dest.is_empty() {
+                    continue;
+                }
+
+                let (tile_x, tile_y) = match (column == 1, row == 1) {
+                    (true, true) => (
+                        self.center == SliceMode::Tile,
+                        self.center == SliceMode::Tile,
+                    ),
+                    (true, false) => (self.edges == SliceMode::Tile, false),
+                    (false, true) => (false, self.edges == SliceMode::Tile),
+                    (false, false) => (false, false),
+                };
+                self.draw_slice(renderer, src, dest, tile_x, tile_y);
+            }
+        }
+    }
+
+    /// Fill `dest` with the `src` area of the image, repeating it along the tiled axes
+    /// and scaling it along the others
+    fn draw_slice<R: Renderer + ?Sized>(
+        &self,
+        renderer: &mut R,
+        src: Rect,
+        dest: Rect,
+        tile_x: bool,
+        tile_y: bool,
+    ) {
+        let tile_w = if tile_x { src.width() } else { dest.width() };
+        let tile_h = if tile_y { src.height() } else { dest.height() };
+        let clip = dest.intersection(&Rect::new(0, 0, renderer.width(), renderer.height()));
+
+        let mut y = dest.top();
+        while y < clip.bottom() {
+            let h = cmp::min(tile_h, (dest.bottom() - y) as u32);
+            let src_h = if tile_y { h } else { src.height() };
+            let mut x = dest.left();
+            while x < clip.right() {
+                let w = cmp::min(tile_w, (dest.right() - x) as u32);
+                let src_w = if tile_x { w } else { src.width() };
+                if x + w as i32 > clip.left() && y + h as i32 > clip.top() {
+                    let roi = self
+                        .image
+                        .roi(&Rect::new(src.left(), src.top(), src_w, src_h));
+                    renderer.draw_image_scaled(&roi, Rect::new(x, y, w, h), self.filter);
+                }
+                x += w as i32;
+            }
+            y += h as i32;
+        }
+    }
+}
+
+/// Sizes of two borders in an area, shrunk proportionally if they do not fit
+fn fit(start: u32, end: u32, size: u32) -> (u32, u32) {
+    let total = start as u64 + end as u64;
+    if total <= size as u64 {
+        (start, end)
+    } else {
+        let start = (start as u64 * size as u64 / total) as u32;
+        (start, size - start)
+    }
+}
@@ -0,0 +1,32 @@
+// SPDX-License-Identifier: MIT
+
+//! Splitting whole-buffer operations into horizontal bands of rows, which are processed on
+//! the rayon thread pool with the `parallel` feature.
+
+/// Buffers with fewer items than this are processed on the calling thread
+#[cfg(feature = "parallel")]
+const MIN_LEN: usize = 1 << 16;
+
+/// Call `f` with horizontal bands of `data`, which holds rows of `w` items, and the index of
+/// the first row of each band. With the `parallel` feature, the bands of large buffers are
+/// processed at the same time.
+#[cfg_attr(not(feature = "parallel"), allow(unused_variables))]
+pub(crate) fn for_each_band<T, F>(data: &mut [T], w: usize, f: F)
+where
+    T: Send,
+    F: Fn(usize, &mut [T]) + Sync,
+{
+    #[cfg(feature = "parallel")]
+    if w > 0 && data.len() >= MIN_LEN {
+        use rayon::prelude::*;
+
+        // A few bands per thread, so that uneven work still spreads out
+        let rows = data.len().div_ceil(w);
+        let band_rows = rows.div_ceil(rayon::current_num_threads() * 4);
+        data.par_chunks_mut(band_rows * w)
+            .enumerate()
+            .for_each(|(i, band)| f(i * band_rows, band));
+        return;
+    }
+    f(0, data);
+}
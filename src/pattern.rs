@@ -0,0 +1,151 @@
+// SPDX-License-Identifier: MIT
+
+//! Repeating image patterns for filling shapes.
+
+use core::cell::Cell;
+
+use crate::color::Color;
+use crate::image::{Image, ImageRoi};
+use crate::rect::Rect;
+use crate::renderer::Renderer;
+use crate::Mode;
+
+/// Along which axes a pattern repeats
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Repeat {
+    #[default]
+    Repeat,
+    RepeatX,
+    RepeatY,
+    /// Draw the image once, leaving the rest of the shape untouched
+    NoRepeat,
+}
+
+/// An image repeated over the plane, used to fill shapes instead of a solid color
+pub struct Pattern<'a> {
+    image: ImageRoi<'a>,
+    /// Renderer position of the top left corner of one repetition of the image
+    pub offset: (i32, i32),
+    pub repeat: Repeat,
+}
+
+impl<'a> Pattern<'a> {
+    /// A pattern repeating the whole image from (0, 0)
+    pub fn new(image: &'a Image) -> Self {
+        Self::from_roi(image.roi(&Rect::new(0, 0, image.width(), image.height())))
+    }
+
+    /// A pattern repeating part of an image from (0, 0)
+    pub fn from_roi(image: ImageRoi<'a>) -> Self {
+        Pattern {
+            image,
+            offset: (0, 0),
+            repeat: Repeat::Repeat,
+        }
+    }
+
+    pub fn with_offset(mut self, x: i32, y: i32) -> Self {
+        self.offset = (x, y);
+        self
+    }
+
+    pub fn with_repeat(mut self, repeat: Repeat) -> Self {
+        self.repeat = repeat;
+        self
+    }
+
+    /// The color of the pattern at a renderer position, `None` outside of a non-repeating image
+    pub fn color_at(&self, x: i32, y: i32) -> Option<Color> {
+        let (w, h) = (self.image.width() as i32, self.image.height() as i32);
+        if w == 0 || h == 0 {
+            return None;
+        }
+        let (repeat_x, repeat_y) = match self.repeat {
+            Repeat::Repeat => (true, true),
+            Repeat::RepeatX => (true, false),
+            Repeat::RepeatY => (false, true),
+            Repeat::NoRepeat => (false, false),
+        };
+        let wrap = |value: i32, size: i32, repeat: bool| {
+            if repeat {
+                Some(value.rem_euclid(size))
+            } else if (0..size).contains(&value) {
+                Some(value)
+            } else {
+                None
+            }
+        };
+        let x = wrap(x - self.offset.0, w, repeat_x)?;
+        let y = wrap(y - self.offset.1, h, repeat_y)?;
+        Some(self.image.get_clamped(x, y))
+    }
+}
+
+/// Draws with a pattern instead of the color given to `pixel` and `rect`,
+/// so every filled shape built from them can be drawn with a pattern
+pub(crate) struct Painter<'r, 'p, R: Renderer + ?Sized> {
+    renderer: &'r mut R,
+    pattern: &'p Pattern<'p>,
+}
+
+impl<'r, 'p, R: Renderer + ?Sized> Painter<'r, 'p, R> {
+    pub(crate) fn new(renderer: &'r mut R, pattern: &'p Pattern<'p>) -> Self {
+        Painter { renderer, pattern }
+    }
+}
+
+impl<R: Renderer + ?Sized> Renderer for Painter<'_, '_, R> {
+    fn width(&self) -> u32 {
+        self.renderer.width()
+    }
+
+    fn height(&self) -> u32 {
+        self.renderer.height()
+    }
+
+    fn data(&self) -> &[Color] {
+        self.renderer.data()
+    }
+
+    fn stride(&self) -> usize {
+        self.renderer.stride()
+    }
+
+    fn data_mut(&mut self) -> &mut [Color] {
+        self.renderer.data_mut()
+    }
+
+    fn sync(&mut self) -> bool {
+        self.renderer.sync()
+    }
+
+    fn update(&mut self) -> bool {
+        self.renderer.update()
+    }
+
+    fn update_rects(&mut self, rects: &[(i32, i32, u32, u32)]) -> bool {
+        self.renderer.update_rects(rects)
+    }
+
+    fn mode(&self) -> &Cell<Mode> {
+        self.renderer.mode()
+    }
+
+    fn pixel(&mut self, x: i32, y: i32, _color: Color) {
+        if let Some(color) = self.pattern.color_at(x, y) {
+            self.renderer.pixel(x, y, color);
+        }
+    }
+
+    fn rect(&mut self, x: i32, y: i32, w: u32, h: u32, _color: Color) {
+        let clip =
+            Rect::new(x, y, w, h).intersection(&Rect::new(0, 0, self.width(), self.height()));
+        for y in clip.top()..clip.bottom() {
+            for x in clip.left()..clip.right() {
+                if let Some(color) = self.pattern.color_at(x, y) {
+                    self.renderer.pixel(x, y, color);
+                }
+            }
+        }
+    }
+}
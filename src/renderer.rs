@@ -1,16 +1,33 @@
 // SPDX-License-Identifier: MIT
 
+#[cfg(not(feature = "std"))]
+use alloc::boxed::Box;
+
 use core::cell::Cell;
 use core::cmp;
 
+use crate::blit;
 #[cfg(feature = "std")]
 use crate::blur;
 use crate::color::Color;
+use crate::flood::{self, Connectivity};
 use crate::graphicspath::GraphicsPath;
 use crate::graphicspath::PointType;
+use crate::gray::GrayImage;
 use crate::image::ImageRoi;
 use crate::image::ImageRoiMut;
+use crate::math;
+#[cfg(feature = "std")]
+use crate::parallel;
+use crate::pattern::{Painter, Pattern};
 use crate::rect::Rect;
+use crate::resample::{self, Filter};
+use crate::simd;
+use crate::subpixel::{self, Path};
+#[cfg(any(feature = "unifont", feature = "unifont-subset"))]
+use crate::text::{self, Font, TextStyle, Unifont};
+use crate::transform::Affine;
+use crate::triangle::{self, Mapping, Texture, Vertex};
 use crate::Mode;
 
 /// The trait to allow rendering code to be placed.
@@ -28,6 +45,12 @@ pub trait Renderer {
     /// Access the pixel buffer mutably
     fn data_mut(&mut self) -> &mut [Color];
 
+    /// Pixels from the start of one row of the pixel buffer to the next, at least the width.
+    /// The buffer may end right after the last pixel of the last row.
+    fn stride(&self) -> usize {
+        self.width() as usize
+    }
+
     /// Update the hardware buffer
     fn sync(&mut self) -> bool;
 
@@ -40,17 +63,11 @@ pub trait Renderer {
     /// Set/get drawing mode
     fn mode(&self) -> &Cell<Mode>;
 
-    fn roi<'a>(&'a self, rect: &Rect) -> ImageRoi<'a>
-    where
-        Self: Sized,
-    {
+    fn roi<'a>(&'a self, rect: &Rect) -> ImageRoi<'a> {
         ImageRoi::from_renderer(self, rect)
     }
 
-    fn roi_mut<'a>(&'a mut self, rect: &Rect) -> ImageRoiMut<'a>
-    where
-        Self: Sized,
-    {
+    fn roi_mut<'a>(&'a mut self, rect: &Rect) -> ImageRoiMut<'a> {
         ImageRoiMut::from_renderer(self, rect)
     }
 
@@ -63,12 +80,13 @@ pub trait Renderer {
         };
         let w = self.width() as i32;
         let h = self.height() as i32;
+        let stride = self.stride();
         let data = self.data_mut();
 
         if x >= 0 && y >= 0 && x < w && y < h {
             let new = color.data;
             let alpha = (new >> 24) & 0xFF;
-            let old = &mut data[(y * w + x) as usize].data;
+            let old = &mut data[y as usize * stride + x as usize].data;
 
             if alpha >= 255 || replace {
                 *old = new;
@@ -289,33 +307,69 @@ pub trait Renderer {
         }
     }
 
+    /// Fill the inside of a path with the nonzero winding rule, closing every subpath
+    fn fill_path(&mut self, graphicspath: &GraphicsPath, color: Color) {
+        let clip = Rect::new(0, 0, self.width(), self.height());
+        graphicspath.fill_spans(&clip, |x, y, w| self.rect(x, y, w, 1, color));
+    }
+
+    /// Fill the inside of a path with a pattern
+    fn fill_path_pattern(&mut self, graphicspath: &GraphicsPath, pattern: &Pattern) {
+        Painter::new(self, pattern).fill_path(graphicspath, Color::BLACK);
+    }
+
     /// Draw a character, using the loaded font
-    #[cfg(feature = "unifont")]
+    #[cfg(any(feature = "unifont", feature = "unifont-subset"))]
     fn char(&mut self, x: i32, y: i32, c: char, color: Color) {
-        let mut offset = (c as usize) * 16;
-        for row in 0..16 {
-            let row_data = if offset < crate::FONT.len() {
-                crate::FONT[offset]
-            } else {
-                0
-            };
-
+        let glyph = crate::font::char_glyph(c);
+        for (row, row_data) in glyph.iter().enumerate() {
             for col in 0..8 {
                 let pixel = (row_data >> (7 - col)) & 1;
                 if pixel > 0 {
-                    self.pixel(x + col, y + row, color);
+                    self.pixel(x + col, y + row as i32, color);
                 }
             }
-            offset += 1;
         }
     }
 
+    /// Draw a character with scaling and effects, using the loaded font
+    #[cfg(any(feature = "unifont", feature = "unifont-subset"))]
+    fn char_styled(&mut self, x: i32, y: i32, c: char, color: Color, style: &TextStyle) {
+        let mut buf = [0; 4];
+        self.text(x, y, c.encode_utf8(&mut buf), color, style);
+    }
+
+    /// Draw a string with scaling and effects, using the loaded font.
+    /// A newline moves back to `x`, one line height down.
+    #[cfg(any(feature = "unifont", feature = "unifont-subset"))]
+    fn text(&mut self, x: i32, y: i32, text: &str, color: Color, style: &TextStyle) {
+        text::draw_text(self, x, y, text, color, &Unifont, style);
+    }
+
+    /// Draw a string with scaling and effects, using the given font or chain of fonts.
+    /// A newline moves back to `x`, one line height down.
+    #[cfg(any(feature = "unifont", feature = "unifont-subset"))]
+    fn text_font(
+        &mut self,
+        x: i32,
+        y: i32,
+        text: &str,
+        color: Color,
+        font: &dyn Font,
+        style: &TextStyle,
+    ) {
+        text::draw_text(self, x, y, text, color, font, style);
+    }
+
     /// Set entire window to a color
     fn set(&mut self, color: Color) {
-        let data = self.data_mut();
-        let data_ptr = data.as_mut_ptr();
-        for i in 0..data.len() as isize {
-            unsafe { *data_ptr.offset(i) = color }
+        let (w, stride) = (self.width() as usize, self.stride());
+        if stride == w {
+            self.data_mut().fill(color);
+        } else {
+            for row in self.data_mut().chunks_mut(stride) {
+                row[..w].fill(color);
+            }
         }
     }
 
@@ -345,40 +399,42 @@ pub trait Renderer {
         let len = cmp::max(start_x, cmp::min(self_w as i32, x + w as i32)) - start_x;
 
         let alpha = (color.data >> 24) & 0xFF;
+        let stride = self.stride();
         let data = self.data_mut();
-        let data_ptr = data.as_mut_ptr();
-        if alpha >= 255 || replace {
-            for y in start_y..end_y {
-                let start = (y * self_w as i32 + start_x) as isize;
-                let end = start + len as isize;
-                for i in start..end {
-                    unsafe {
-                        *data_ptr.offset(i) = color;
-                    }
-                }
-            }
-        } else {
-            let n_alpha = 255 - alpha;
-            let new = color.data;
-
-            for y in start_y..end_y {
-                let start = (y * self_w as i32 + start_x) as isize;
-                let end = start + len as isize;
-                for i in start..end {
-                    unsafe {
-                        let old = (data_ptr as *mut u32).offset(i);
-                        let rb =
-                            ((n_alpha * (*old & 0x00FF00FF)) + (alpha * (new & 0x00FF00FF))) >> 8;
-                        let ag = (n_alpha * ((*old & 0xFF00FF00) >> 8))
-                            + (alpha * (0x01000000 | ((new & 0x0000FF00) >> 8)));
-
-                        *old = (rb & 0x00FF00FF) | (ag & 0xFF00FF00);
-                    }
-                }
+        for y in start_y..end_y {
+            let start = y as usize * stride + start_x as usize;
+            let row = &mut data[start..start + len as usize];
+            if alpha >= 255 || replace {
+                row.fill(color);
+            } else {
+                simd::blend_color(row, color);
             }
         }
     }
 
+    /// Fill a rectangle with a pattern
+    fn rect_pattern(&mut self, x: i32, y: i32, w: u32, h: u32, pattern: &Pattern) {
+        Painter::new(self, pattern).rect(x, y, w, h, Color::BLACK);
+    }
+
+    /// Fill a circle with a pattern
+    fn circle_pattern(&mut self, x0: i32, y0: i32, radius: u32, pattern: &Pattern) {
+        Painter::new(self, pattern).circle(x0, y0, -(radius as i32), Color::BLACK);
+    }
+
+    /// Fill a rounded rectangle with a pattern
+    fn rounded_rect_pattern(
+        &mut self,
+        x: i32,
+        y: i32,
+        w: u32,
+        h: u32,
+        radius: u32,
+        pattern: &Pattern,
+    ) {
+        Painter::new(self, pattern).rounded_rect(x, y, w, h, radius, true, Color::BLACK);
+    }
+
     #[cfg(feature = "std")]
     fn box_blur(&mut self, x: i32, y: i32, w: u32, h: u32, r: i32) {
         let self_w = self.width();
@@ -390,11 +446,12 @@ pub trait Renderer {
         let start_x = cmp::max(0, cmp::min(self_w as i32 - 1, x));
         let end_x = cmp::max(start_x, cmp::min(self_w as i32, x + w as i32));
 
+        let stride = self.stride();
         let data = self.data_mut();
         let mut blur_data: Vec<Color> = Vec::new();
         for y in start_y..end_y {
             for x in start_x..end_x {
-                let old = data[y as usize * self_w as usize + x as usize];
+                let old = data[y as usize * stride + x as usize];
                 blur_data.push(old);
             }
         }
@@ -406,7 +463,7 @@ pub trait Renderer {
         for y in start_y..end_y {
             for x in start_x..end_x {
                 let a = blur_data[counter as usize];
-                let old = &mut data[y as usize * self_w as usize + x as usize].data;
+                let old = &mut data[y as usize * stride + x as usize].data;
 
                 *old = a.data;
                 counter += 1;
@@ -497,104 +554,95 @@ pub trait Renderer {
 
     ///Display an image overwriting a portion of window starting at given line : very quick!!
     fn image_over(&mut self, start: i32, image_data: &[Color]) {
-        let start = start as usize * self.width() as usize;
-        let window_data = self.data_mut();
-        let stop = cmp::min(start + image_data.len(), window_data.len());
-        let end = cmp::min(image_data.len(), window_data.len() - start);
-
-        window_data[start..stop].copy_from_slice(&image_data[..end]);
+        let width = self.width();
+        let rows = image_data.len().div_ceil(cmp::max(width, 1) as usize);
+        let dest = Rect::new(0, start, width, rows as u32);
+        blit::blit(self, dest, image_data, width as usize, Mode::Overwrite);
     }
 
-    ///Display an image using non transparent method
+    ///Display an image using non transparent method, clipped to the window
     #[inline(always)]
     fn image_opaque(&mut self, start_x: i32, start_y: i32, w: u32, h: u32, image_data: &[Color]) {
-        let w = w as usize;
-        let mut h = h as usize;
-        let width = self.width() as usize;
-        let height = self.height() as usize;
-        let start_x = start_x as usize;
-        let start_y = start_y as usize;
-
-        //check boundaries
-        if start_x >= width || start_y >= height {
-            return;
-        }
-        if h + start_y > height {
-            h = height - start_y;
-        }
-        let window_data = self.data_mut();
-        let offset = start_y * width + start_x;
-        //copy image slices to window line by line
-        for l in 0..h {
-            let start = offset + l * width;
-            let mut stop = start + w;
-            let begin = l * w;
-            let mut end = begin + w;
-            //check boundaries
-            if start_x + w > width {
-                stop = (start_y + l + 1) * width - 1;
-                end = begin + stop - start;
-            }
-            window_data[start..stop].copy_from_slice(&image_data[begin..end]);
-        }
+        let dest = Rect::new(start_x, start_y, w, h);
+        blit::blit(self, dest, image_data, w as usize, Mode::Overwrite);
     }
 
     // Speed improved, image can be outside of window boundary
     #[inline(always)]
     fn image_fast(&mut self, start_x: i32, start_y: i32, w: u32, h: u32, image_data: &[Color]) {
-        let w = w as usize;
-        let h = h as usize;
-        let width = self.width() as usize;
-        let height = self.height() as usize;
+        let dest = Rect::new(start_x, start_y, w, h);
+        blit::blit(self, dest, image_data, w as usize, Mode::Blend);
+    }
+
+    /// Draw an image whose rows start `stride` pixels apart in `data`, blending it or
+    /// overwriting with it by the mode. Rows are cut short where `data` ends.
+    fn image_stride(
+        &mut self,
+        start_x: i32,
+        start_y: i32,
+        w: u32,
+        h: u32,
+        stride: usize,
+        data: &[Color],
+    ) {
+        let dest = Rect::new(start_x, start_y, w, h);
+        blit::blit(self, dest, data, stride, self.mode().get());
+    }
 
-        //simply return if image is outside of window
-        if start_x < 0 || start_y < 0 {
+    /// Draw an image region scaled to fill `dest`, sampling the source directly with `filter`.
+    /// Bicubic uses the closest 4x4 pixels and area is the same as bilinear,
+    /// so large downscales look better with `ImageRoi::resample`.
+    fn draw_image_scaled(&mut self, src: &ImageRoi, dest: Rect, filter: Filter) {
+        if src.width() == 0 || src.height() == 0 || dest.is_empty() {
             return;
         }
-        let start_x = start_x as usize;
-        let start_y = start_y as usize;
-        if start_x >= width || start_y >= height {
-            return;
+        let clip = dest.intersection(&Rect::new(0, 0, self.width(), self.height()));
+        let scale_x = src.width() as f32 / dest.width() as f32;
+        let scale_y = src.height() as f32 / dest.height() as f32;
+        for y in clip.top()..clip.bottom() {
+            let v = ((y - dest.top()) as f32 + 0.5) * scale_y;
+            for x in clip.left()..clip.right() {
+                let u = ((x - dest.left()) as f32 + 0.5) * scale_x;
+                self.pixel(x, y, resample::sample(src, u, v, filter));
+            }
         }
+    }
 
-        //check boundaries
-        let active_w = if start_x + w > width {
-            width - start_x
-        } else {
-            w
+    /// Draw an image region with `transform` mapping its pixel coordinates to the renderer's,
+    /// sampling the source directly with `filter`
+    fn draw_image_transformed(&mut self, src: &ImageRoi, transform: &Affine, filter: Filter) {
+        let (w, h) = (src.width() as f32, src.height() as f32);
+        let Some(inverse) = transform.invert() else {
+            return;
         };
+        if src.width() == 0 || src.height() == 0 {
+            return;
+        }
 
-        let window_data = self.data_mut();
-        let offset = start_y * width + start_x;
-
-        //copy image slices to window line by line
-        for l in 0..h {
-            if start_y + l >= height {
-                break;
-            }
-
-            let win_start = offset + l * width;
-            let img_start = l * w;
-            let row_w = active_w.min(image_data.len() - img_start);
-            let Some(target_slice) = window_data.get_mut(win_start..win_start + row_w) else {
-                break;
-            };
-            let Some(source_slice) = image_data.get(img_start..img_start + row_w) else {
-                break;
-            };
-
-            for (old_pixel, new_pixel) in target_slice.iter_mut().zip(source_slice.iter()) {
-                let new = new_pixel.data;
-                let old = old_pixel.data;
-
-                let alpha = (new >> 24) & 0xFF;
-                let n_alpha = 255 - alpha;
-
-                let rb = ((n_alpha * (old & 0x00FF00FF)) + (alpha * (new & 0x00FF00FF))) >> 8;
-                let ag = (n_alpha * ((old & 0xFF00FF00) >> 8))
-                    + (alpha * (0x01000000 | ((new & 0x0000FF00) >> 8)));
-
-                old_pixel.data = (rb & 0x00FF00FF) | (ag & 0xFF00FF00);
+        let corners = [
+            transform.apply(0.0, 0.0),
+            transform.apply(w, 0.0),
+            transform.apply(0.0, h),
+            transform.apply(w, h),
+        ];
+        let (mut min_x, mut min_y) = corners[0];
+        let (mut max_x, mut max_y) = corners[0];
+        for &(x, y) in corners.iter() {
+            min_x = min_x.min(x);
+            min_y = min_y.min(y);
+            max_x = max_x.max(x);
+            max_y = max_y.max(y);
+        }
+        let (x0, y0) = (math::floor(min_x).max(0), math::floor(min_y).max(0));
+        let x1 = math::ceil(max_x).min(self.width() as i32);
+        let y1 = math::ceil(max_y).min(self.height() as i32);
+
+        for y in y0..y1 {
+            for x in x0..x1 {
+                let (u, v) = inverse.apply(x as f32 + 0.5, y as f32 + 0.5);
+                if u >= 0.0 && v >= 0.0 && u < w && v < h {
+                    self.pixel(x, y, resample::sample(src, u, v, filter));
+                }
             }
         }
     }
@@ -625,27 +673,51 @@ pub trait Renderer {
             }
         }
 
+        let replace = match self.mode().get() {
+            Mode::Blend => false,
+            Mode::Overwrite => true,
+        };
+        let (width, height) = (self.width(), self.height());
+        let clip = Rect::new(rect_x, rect_y, rect_width, rect_height)
+            .intersection(&Rect::new(0, 0, width, height));
+        if clip.is_empty() {
+            return;
+        }
+        let (left, right) = (clip.left() as usize, clip.right() as usize);
+        let top = clip.top();
+        let stride = self.stride();
+        let rows = top as usize * stride..(clip.bottom() as usize - 1) * stride + right;
+
         if (start_x == end_x) && (start_y == end_y) {
             // Degenerate gradient
             self.rect(rect_x, rect_y, rect_width, rect_height, start_color);
         } else if start_x == end_x {
             // Vertical gradient
             let y_factor = 1.0 / (end_y - start_y) as f64;
-            for y in rect_y..(rect_y + rect_height as i32) {
-                let proj = (y - start_y) as f64 * y_factor;
-                let scale = clamp(proj);
-                let color = Color::interpolate(start_color, end_color, scale);
-                self.line(rect_x, y, rect_x + rect_width as i32 - 1, y, color);
-            }
+            parallel::for_each_band(&mut self.data_mut()[rows], stride, |y0, band| {
+                for (i, row) in band.chunks_mut(stride).enumerate() {
+                    let y = top + (y0 + i) as i32;
+                    let proj = (y - start_y) as f64 * y_factor;
+                    let scale = clamp(proj);
+                    let color = Color::interpolate(start_color, end_color, scale);
+                    simd::draw_color(&mut row[left..right], color, replace);
+                }
+            });
         } else if start_y == end_y {
-            // Horizontal gradient
+            // Horizontal gradient, the same colors on every row
             let x_factor = 1.0 / (end_x - start_x) as f64;
-            for x in rect_x..(rect_x + rect_width as i32) {
-                let proj = (x - start_x) as f64 * x_factor;
-                let scale = clamp(proj);
-                let color = Color::interpolate(start_color, end_color, scale);
-                self.line(x, rect_y, x, rect_y + rect_height as i32 - 1, color);
-            }
+            let colors: Vec<Color> = (clip.left()..clip.right())
+                .map(|x| {
+                    let proj = (x - start_x) as f64 * x_factor;
+                    let scale = clamp(proj);
+                    Color::interpolate(start_color, end_color, scale)
+                })
+                .collect();
+            parallel::for_each_band(&mut self.data_mut()[rows], stride, |_, band| {
+                for row in band.chunks_mut(stride) {
+                    simd::draw(&mut row[left..right], &colors, replace);
+                }
+            });
         } else {
             // Non axis-aligned gradient
             // Gradient vector
@@ -653,20 +725,25 @@ pub trait Renderer {
             let grad_y = (end_y - start_y) as f64;
             let grad_len = 1.0 / (grad_x * grad_x + grad_y * grad_y);
 
-            for y in rect_y..(rect_y + rect_height as i32) {
-                for x in rect_x..(rect_x + rect_width as i32) {
-                    // Pixel vector
-                    let pix_x = (x - start_x) as f64;
-                    let pix_y = (y - start_y) as f64;
-                    // Scalar projection
-                    let proj = (pix_x * grad_x + pix_y * grad_y) * grad_len;
-                    // Saturation
-                    let scale = clamp(proj);
-                    // Interpolation
-                    let color = Color::interpolate(start_color, end_color, scale);
-                    self.pixel(x, y, color);
+            parallel::for_each_band(&mut self.data_mut()[rows], stride, |y0, band| {
+                let mut colors = Vec::with_capacity(right - left);
+                for (i, row) in band.chunks_mut(stride).enumerate() {
+                    let y = top + (y0 + i) as i32;
+                    colors.clear();
+                    colors.extend((clip.left()..clip.right()).map(|x| {
+                        // Pixel vector
+                        let pix_x = (x - start_x) as f64;
+                        let pix_y = (y - start_y) as f64;
+                        // Scalar projection
+                        let proj = (pix_x * grad_x + pix_y * grad_y) * grad_len;
+                        // Saturation
+                        let scale = clamp(proj);
+                        // Interpolation
+                        Color::interpolate(start_color, end_color, scale)
+                    }));
+                    simd::draw(&mut row[left..right], &colors, replace);
                 }
-            }
+            });
         }
     }
 
@@ -918,12 +995,503 @@ pub trait Renderer {
         }
     }
 
+    /// Fill a rectangle with fractional coordinates, blending the pixels on its edges by how
+    /// much of them it covers
+    fn rect_f(&mut self, x: f32, y: f32, w: f32, h: f32, color: Color) {
+        subpixel::fill_rect(self, x, y, w, h, color);
+    }
+
+    /// Draw an anti-aliased line of `width` pixels between fractional coordinates, with square
+    /// ends at the points
+    #[cfg(feature = "std")]
+    fn line_f(&mut self, x0: f32, y0: f32, x1: f32, y1: f32, width: f32, color: Color) {
+        subpixel::line(self, [x0, y0], [x1, y1], width, color);
+    }
+
+    /// Fill an anti-aliased circle with a fractional center and radius
+    #[cfg(feature = "std")]
+    fn fill_circle_f(&mut self, x: f32, y: f32, radius: f32, color: Color) {
+        subpixel::circle_ring(self, [x, y], radius, 0.0, color);
+    }
+
+    /// Draw an anti-aliased circle outline of `width` pixels, centered on the radius
+    #[cfg(feature = "std")]
+    fn stroke_circle_f(&mut self, x: f32, y: f32, radius: f32, width: f32, color: Color) {
+        let half = width / 2.0;
+        subpixel::circle_ring(self, [x, y], radius + half, radius - half, color);
+    }
+
+    /// Fill the inside of a path with fractional coordinates with the nonzero winding rule,
+    /// anti-aliasing its edges
+    fn fill_path_f(&mut self, path: &Path, color: Color) {
+        subpixel::fill_path(self, path, color);
+    }
+
+    /// Draw the lines of a path with fractional coordinates, `width` pixels wide with round
+    /// joins and square ends
+    #[cfg(feature = "std")]
+    fn stroke_path_f(&mut self, path: &Path, width: f32, color: Color) {
+        subpixel::stroke_path(self, path, width, color);
+    }
+
+    /// Draw an image region scaled to a rect with fractional coordinates, sampling it with
+    /// `filter` and blending the pixels on its edges by how much of them it covers
+    fn draw_image_f(&mut self, src: &ImageRoi, x: f32, y: f32, w: f32, h: f32, filter: Filter) {
+        subpixel::image(self, src, x, y, w, h, filter);
+    }
+
+    /// Fill a triangle, interpolating the colors of its vertices. Pixels are filled when their
+    /// centers are inside, and triangles sharing an edge fill each pixel on it once.
+    fn fill_triangle(&mut self, vertices: &[Vertex; 3]) {
+        triangle::fill(self, vertices, None);
+    }
+
+    /// Fill a triangle with a texture at the texture coordinates of its vertices, sampled with
+    /// `filter` and multiplied by the interpolated vertex colors
+    fn fill_triangle_textured(
+        &mut self,
+        vertices: &[Vertex; 3],
+        texture: &ImageRoi,
+        filter: Filter,
+        mapping: Mapping,
+    ) {
+        let texture = Texture {
+            image: texture,
+            filter,
+            mapping,
+        };
+        triangle::fill(self, vertices, Some(&texture));
+    }
+
+    /// Fill the triangles of a mesh, each given by three indices into `vertices`
+    fn fill_mesh(&mut self, vertices: &[Vertex], indices: &[[u32; 3]]) {
+        triangle::fill_mesh(self, vertices, indices, None);
+    }
+
+    /// Fill the triangles of a mesh with a texture, as `fill_triangle_textured` does
+    fn fill_mesh_textured(
+        &mut self,
+        vertices: &[Vertex],
+        indices: &[[u32; 3]],
+        texture: &ImageRoi,
+        filter: Filter,
+        mapping: Mapping,
+    ) {
+        let texture = Texture {
+            image: texture,
+            filter,
+            mapping,
+        };
+        triangle::fill_mesh(self, vertices, indices, Some(&texture));
+    }
+
+    /// Fill the pixels connected to (x, y) whose colors are within `tolerance` of its color on
+    /// every channel, like a paint bucket
+    fn flood_fill(
+        &mut self,
+        x: i32,
+        y: i32,
+        color: Color,
+        tolerance: u8,
+        connectivity: Connectivity,
+    ) {
+        flood::fill(self, x, y, color, tolerance, connectivity);
+    }
+
+    /// The pixels `flood_fill` would fill, as a mask of 255 inside and 0 outside, without
+    /// changing any of them, like a magic wand selection
+    fn flood_region(&self, x: i32, y: i32, tolerance: u8, connectivity: Connectivity) -> GrayImage {
+        flood::region(self, x, y, tolerance, connectivity)
+    }
+
     ///Gets pixel color at x,y position
     fn getpixel(&self, x: i32, y: i32) -> Color {
-        let p = (self.width() as i32 * y + x) as usize;
+        let p = (self.stride() as i32 * y + x) as usize;
         if p >= self.data().len() {
             return Color::rgba(0, 0, 0, 0);
         }
         self.data()[p]
     }
 }
+
+/// Implement `Renderer` for a pointer to a renderer, forwarding every method so that the
+/// pointee's own implementations are used
+macro_rules! forward_renderer {
+    ($ty:ty) => {
+        impl<R: Renderer + ?Sized> Renderer for $ty {
+            fn width(&self) -> u32 {
+                (**self).width()
+            }
+
+            fn height(&self) -> u32 {
+                (**self).height()
+            }
+
+            fn data(&self) -> &[Color] {
+                (**self).data()
+            }
+
+            fn data_mut(&mut self) -> &mut [Color] {
+                (**self).data_mut()
+            }
+
+            fn stride(&self) -> usize {
+                (**self).stride()
+            }
+
+            fn sync(&mut self) -> bool {
+                (**self).sync()
+            }
+
+            fn update(&mut self) -> bool {
+                (**self).update()
+            }
+
+            fn update_rects(&mut self, rects: &[(i32, i32, u32, u32)]) -> bool {
+                (**self).update_rects(rects)
+            }
+
+            fn mode(&self) -> &Cell<Mode> {
+                (**self).mode()
+            }
+
+            fn roi<'a>(&'a self, rect: &Rect) -> ImageRoi<'a> {
+                (**self).roi(rect)
+            }
+
+            fn roi_mut<'a>(&'a mut self, rect: &Rect) -> ImageRoiMut<'a> {
+                (**self).roi_mut(rect)
+            }
+
+            fn pixel(&mut self, x: i32, y: i32, color: Color) {
+                (**self).pixel(x, y, color)
+            }
+
+            fn arc(&mut self, x0: i32, y0: i32, radius: i32, parts: u8, color: Color) {
+                (**self).arc(x0, y0, radius, parts, color)
+            }
+
+            fn circle(&mut self, x0: i32, y0: i32, radius: i32, color: Color) {
+                (**self).circle(x0, y0, radius, color)
+            }
+
+            fn line4points(&mut self, x0: i32, y0: i32, x: i32, y: i32, color: Color) {
+                (**self).line4points(x0, y0, x, y, color)
+            }
+
+            fn line(&mut self, argx1: i32, argy1: i32, argx2: i32, argy2: i32, color: Color) {
+                (**self).line(argx1, argy1, argx2, argy2, color)
+            }
+
+            fn lines(&mut self, points: &[[i32; 2]], color: Color) {
+                (**self).lines(points, color)
+            }
+
+            fn draw_path_stroke(&mut self, graphicspath: GraphicsPath, color: Color) {
+                (**self).draw_path_stroke(graphicspath, color)
+            }
+
+            fn fill_path(&mut self, graphicspath: &GraphicsPath, color: Color) {
+                (**self).fill_path(graphicspath, color)
+            }
+
+            fn fill_path_pattern(&mut self, graphicspath: &GraphicsPath, pattern: &Pattern) {
+                (**self).fill_path_pattern(graphicspath, pattern)
+            }
+
+            #[cfg(any(feature = "unifont", feature = "unifont-subset"))]
+            fn char(&mut self, x: i32, y: i32, c: char, color: Color) {
+                (**self).char(x, y, c, color)
+            }
+
+            #[cfg(any(feature = "unifont", feature = "unifont-subset"))]
+            fn char_styled(&mut self, x: i32, y: i32, c: char, color: Color, style: &TextStyle) {
+                (**self).char_styled(x, y, c, color, style)
+            }
+
+            #[cfg(any(feature = "unifont", feature = "unifont-subset"))]
+            fn text(&mut self, x: i32, y: i32, text: &str, color: Color, style: &TextStyle) {
+                (**self).text(x, y, text, color, style)
+            }
+
+            #[cfg(any(feature = "unifont", feature = "unifont-subset"))]
+            fn text_font(
+                &mut self,
+                x: i32,
+                y: i32,
+                text: &str,
+                color: Color,
+                font: &dyn Font,
+                style: &TextStyle,
+            ) {
+                (**self).text_font(x, y, text, color, font, style)
+            }
+
+            fn set(&mut self, color: Color) {
+                (**self).set(color)
+            }
+
+            fn clear(&mut self) {
+                (**self).clear()
+            }
+
+            fn solid_rect(&mut self, rect: &Rect, color: Color) {
+                (**self).solid_rect(rect, color)
+            }
+
+            fn rect(&mut self, x: i32, y: i32, w: u32, h: u32, color: Color) {
+                (**self).rect(x, y, w, h, color)
+            }
+
+            fn rect_pattern(&mut self, x: i32, y: i32, w: u32, h: u32, pattern: &Pattern) {
+                (**self).rect_pattern(x, y, w, h, pattern)
+            }
+
+            fn circle_pattern(&mut self, x0: i32, y0: i32, radius: u32, pattern: &Pattern) {
+                (**self).circle_pattern(x0, y0, radius, pattern)
+            }
+
+            fn rounded_rect_pattern(
+                &mut self,
+                x: i32,
+                y: i32,
+                w: u32,
+                h: u32,
+                radius: u32,
+                pattern: &Pattern,
+            ) {
+                (**self).rounded_rect_pattern(x, y, w, h, radius, pattern)
+            }
+
+            #[cfg(feature = "std")]
+            fn box_blur(&mut self, x: i32, y: i32, w: u32, h: u32, r: i32) {
+                (**self).box_blur(x, y, w, h, r)
+            }
+
+            #[allow(clippy::too_many_arguments)]
+            #[cfg(feature = "std")]
+            fn box_shadow(
+                &mut self,
+                x: i32,
+                y: i32,
+                w: u32,
+                h: u32,
+                offset_x: i32,
+                offset_y: i32,
+                r: i32,
+                color: Color,
+            ) {
+                (**self).box_shadow(x, y, w, h, offset_x, offset_y, r, color)
+            }
+
+            fn image(&mut self, start_x: i32, start_y: i32, w: u32, h: u32, data: &[Color]) {
+                (**self).image(start_x, start_y, w, h, data)
+            }
+
+            fn image_legacy(&mut self, start_x: i32, start_y: i32, w: u32, h: u32, data: &[Color]) {
+                (**self).image_legacy(start_x, start_y, w, h, data)
+            }
+
+            fn image_over(&mut self, start: i32, image_data: &[Color]) {
+                (**self).image_over(start, image_data)
+            }
+
+            fn image_opaque(
+                &mut self,
+                start_x: i32,
+                start_y: i32,
+                w: u32,
+                h: u32,
+                image_data: &[Color],
+            ) {
+                (**self).image_opaque(start_x, start_y, w, h, image_data)
+            }
+
+            fn image_fast(
+                &mut self,
+                start_x: i32,
+                start_y: i32,
+                w: u32,
+                h: u32,
+                image_data: &[Color],
+            ) {
+                (**self).image_fast(start_x, start_y, w, h, image_data)
+            }
+
+            fn image_stride(
+                &mut self,
+                start_x: i32,
+                start_y: i32,
+                w: u32,
+                h: u32,
+                stride: usize,
+                data: &[Color],
+            ) {
+                (**self).image_stride(start_x, start_y, w, h, stride, data)
+            }
+
+            fn draw_image_scaled(&mut self, src: &ImageRoi, dest: Rect, filter: Filter) {
+                (**self).draw_image_scaled(src, dest, filter)
+            }
+
+            fn draw_image_transformed(
+                &mut self,
+                src: &ImageRoi,
+                transform: &Affine,
+                filter: Filter,
+            ) {
+                (**self).draw_image_transformed(src, transform, filter)
+            }
+
+            #[allow(clippy::too_many_arguments)]
+            #[cfg(feature = "std")]
+            fn linear_gradient(
+                &mut self,
+                rect_x: i32,
+                rect_y: i32,
+                rect_width: u32,
+                rect_height: u32,
+                start_x: i32,
+                start_y: i32,
+                end_x: i32,
+                end_y: i32,
+                start_color: Color,
+                end_color: Color,
+            ) {
+                (**self).linear_gradient(
+                    rect_x,
+                    rect_y,
+                    rect_width,
+                    rect_height,
+                    start_x,
+                    start_y,
+                    end_x,
+                    end_y,
+                    start_color,
+                    end_color,
+                )
+            }
+
+            #[allow(clippy::too_many_arguments)]
+            fn rounded_rect(
+                &mut self,
+                x: i32,
+                y: i32,
+                w: u32,
+                h: u32,
+                radius: u32,
+                filled: bool,
+                color: Color,
+            ) {
+                (**self).rounded_rect(x, y, w, h, radius, filled, color)
+            }
+
+            #[cfg(feature = "std")]
+            fn wu_line(&mut self, x0: i32, y0: i32, x1: i32, y1: i32, color: Color) {
+                (**self).wu_line(x0, y0, x1, y1, color)
+            }
+
+            #[cfg(feature = "std")]
+            fn wu_circle(&mut self, x0: i32, y0: i32, radius: i32, color: Color) {
+                (**self).wu_circle(x0, y0, radius, color)
+            }
+
+            fn rect_f(&mut self, x: f32, y: f32, w: f32, h: f32, color: Color) {
+                (**self).rect_f(x, y, w, h, color)
+            }
+
+            #[cfg(feature = "std")]
+            fn line_f(&mut self, x0: f32, y0: f32, x1: f32, y1: f32, width: f32, color: Color) {
+                (**self).line_f(x0, y0, x1, y1, width, color)
+            }
+
+            #[cfg(feature = "std")]
+            fn fill_circle_f(&mut self, x: f32, y: f32, radius: f32, color: Color) {
+                (**self).fill_circle_f(x, y, radius, color)
+            }
+
+            #[cfg(feature = "std")]
+            fn stroke_circle_f(&mut self, x: f32, y: f32, radius: f32, width: f32, color: Color) {
+                (**self).stroke_circle_f(x, y, radius, width, color)
+            }
+
+            fn fill_path_f(&mut self, path: &Path, color: Color) {
+                (**self).fill_path_f(path, color)
+            }
+
+            #[cfg(feature = "std")]
+            fn stroke_path_f(&mut self, path: &Path, width: f32, color: Color) {
+                (**self).stroke_path_f(path, width, color)
+            }
+
+            fn draw_image_f(
+                &mut self,
+                src: &ImageRoi,
+                x: f32,
+                y: f32,
+                w: f32,
+                h: f32,
+                filter: Filter,
+            ) {
+                (**self).draw_image_f(src, x, y, w, h, filter)
+            }
+
+            fn fill_triangle(&mut self, vertices: &[Vertex; 3]) {
+                (**self).fill_triangle(vertices)
+            }
+
+            fn fill_triangle_textured(
+                &mut self,
+                vertices: &[Vertex; 3],
+                texture: &ImageRoi,
+                filter: Filter,
+                mapping: Mapping,
+            ) {
+                (**self).fill_triangle_textured(vertices, texture, filter, mapping)
+            }
+
+            fn fill_mesh(&mut self, vertices: &[Vertex], indices: &[[u32; 3]]) {
+                (**self).fill_mesh(vertices, indices)
+            }
+
+            fn fill_mesh_textured(
+                &mut self,
+                vertices: &[Vertex],
+                indices: &[[u32; 3]],
+                texture: &ImageRoi,
+                filter: Filter,
+                mapping: Mapping,
+            ) {
+                (**self).fill_mesh_textured(vertices, indices, texture, filter, mapping)
+            }
+
+            fn flood_fill(
+                &mut self,
+                x: i32,
+                y: i32,
+                color: Color,
+                tolerance: u8,
+                connectivity: Connectivity,
+            ) {
+                (**self).flood_fill(x, y, color, tolerance, connectivity)
+            }
+
+            fn flood_region(
+                &self,
+                x: i32,
+                y: i32,
+                tolerance: u8,
+                connectivity: Connectivity,
+            ) -> GrayImage {
+                (**self).flood_region(x, y, tolerance, connectivity)
+            }
+
+            fn getpixel(&self, x: i32, y: i32) -> Color {
+                (**self).getpixel(x, y)
+            }
+        }
+    };
+}
+
+forward_renderer!(&mut R);
+forward_renderer!(Box<R>);
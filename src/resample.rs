@@ -0,0 +1,330 @@
+// SPDX-License-Identifier: MIT
+
+//! Image scaling to arbitrary sizes that does not need `std` or the `image` feature.
+//!
+//! Colors are interpolated with premultiplied alpha, so transparent pixels do not darken
+//! the edges of what they surround.
+
+#[cfg(not(feature = "std"))]
+use alloc::{vec, vec::Vec};
+
+use crate::color::Color;
+use crate::image::{Image, ImageRoi};
+use crate::math;
+use crate::parallel;
+
+/// How pixels are sampled when scaling an image
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Filter {
+    /// The closest pixel, keeping hard edges
+    Nearest,
+    /// Linear interpolation between the closest pixels, averaging more pixels when downscaling
+    #[default]
+    Bilinear,
+    /// Catmull-Rom cubic interpolation, sharper than bilinear
+    Bicubic,
+    /// The average of the covered pixels, weighted by coverage, best for downscaling
+    Area,
+}
+
+impl Filter {
+    /// Radius in source pixels of the filter when not downscaling
+    fn support(self) -> f32 {
+        match self {
+            Filter::Nearest | Filter::Area => 0.5,
+            Filter::Bilinear => 1.0,
+            Filter::Bicubic => 2.0,
+        }
+    }
+
+    fn kernel(self, x: f32) -> f32 {
+        let x = x.abs();
+        match self {
+            Filter::Bilinear => (1.0 - x).max(0.0),
+            Filter::Bicubic => {
+                const A: f32 = -0.5;
+                if x < 1.0 {
+                    ((A + 2.0) * x - (A + 3.0)) * x * x + 1.0
+                } else if x < 2.0 {
+                    ((A * x - 5.0 * A) * x + 8.0 * A) * x - 4.0 * A
+                } else {
+                    0.0
+                }
+            }
+            Filter::Nearest | Filter::Area => 1.0,
+        }
+    }
+}
+
+/// The source pixels, starting at `start`, that make up one destination pixel
+struct Contribution {
+    start: usize,
+    weights: Vec<f32>,
+}
+
+/// Compute which source pixels contribute to each destination pixel along one axis
+fn contributions(src_len: u32, dst_len: u32, filter: Filter) -> Vec<Contribution> {
+    let scale = src_len as f32 / dst_len as f32;
+    (0..dst_len)
+        .map(|i| {
+            let center = (i as f32 + 0.5) * scale;
+            let nearest = || Contribution {
+                start: math::floor(center).clamp(0, src_len as i32 - 1) as usize,
+                weights: vec![1.0],
+            };
+
+            let mut contribution = match filter {
+                Filter::Nearest => return nearest(),
+                Filter::Area => {
+                    let (x0, x1) = (i as f32 * scale, (i + 1) as f32 * scale);
+                    let start = math::floor(x0).max(0) as usize;
+                    let end = (math::ceil(x1) as usize).min(src_len as usize);
+                    Contribution {
+                        start,
+                        weights: (start..end)
+                            .map(|j| x1.min(j as f32 + 1.0) - x0.max(j as f32))
+                            .collect(),
+                    }
+                }
+                _ => {
+                    // Widen the filter when downscaling so every source pixel is used
+                    let filter_scale = scale.max(1.0);
+                    let support = filter.support() * filter_scale;
+                    let start = math::floor(center - support).max(0) as usize;
+                    let end = math::ceil(center + support).clamp(0, src_len as i32) as usize;
+                    Contribution {
+                        start,
+                        weights: (start..end)
+                            .map(|j| filter.kernel((j as f32 + 0.5 - center) / filter_scale))
+                            .collect(),
+                    }
+                }
+            };
+
+            let sum: f32 = contribution.weights.iter().sum();
+            if sum.abs() < f32::EPSILON {
+                return nearest();
+            }
+            for weight in contribution.weights.iter_mut() {
+                *weight /= sum;
+            }
+            contribution
+        })
+        .collect()
+}
+
+fn premultiply(color: Color) -> [f32; 4] {
+    let a = color.a() as f32;
+    [
+        color.r() as f32 * a / 255.0,
+        color.g() as f32 * a / 255.0,
+        color.b() as f32 * a / 255.0,
+        a,
+    ]
+}
+
+fn unpremultiply(pixel: [f32; 4]) -> Color {
+    let a = pixel[3];
+    if a < 0.5 {
+        return Color::rgba(0, 0, 0, 0);
+    }
+    // Negative lobes of the bicubic filter can push colors outside of what alpha allows, and
+    // alpha past opaque, so colors are divided by the alpha before it is clamped
+    let channel = |c: f32| math::round((c * 255.0 / a).clamp(0.0, 255.0)) as u8;
+    Color::rgba(
+        channel(pixel[0]),
+        channel(pixel[1]),
+        channel(pixel[2]),
+        math::round(a.min(255.0)) as u8,
+    )
+}
+
+/// Sample a region of an image at (u, v) in its pixel coordinates, where pixel centers are at
+/// half units. Pixels beyond its edges repeat the edge pixels. Area sampling is bilinear here,
+/// as there is no footprint to average over.
+pub(crate) fn sample(src: &ImageRoi, u: f32, v: f32, filter: Filter) -> Color {
+    let radius = match filter {
+        Filter::Nearest => return src.get_clamped(math::floor(u), math::floor(v)),
+        Filter::Bilinear | Filter::Area => 1,
+        Filter::Bicubic => 2,
+    };
+    let kernel = if filter == Filter::Bicubic {
+        Filter::Bicubic
+    } else {
+        Filter::Bilinear
+    };
+
+    let (u, v) = (u - 0.5, v - 0.5);
+    let (x0, y0) = (math::floor(u), math::floor(v));
+    let mut pixel = [0.0; 4];
+    for y in y0 - radius + 1..=y0 + radius {
+        let weight_y = kernel.kernel(v - y as f32);
+        for x in x0 - radius + 1..=x0 + radius {
+            let weight = kernel.kernel(u - x as f32) * weight_y;
+            let src = premultiply(src.get_clamped(x, y));
+            for c in 0..4 {
+                pixel[c] += weight * src[c];
+            }
+        }
+    }
+    unpremultiply(pixel)
+}
+
+/// Scale a region of an image to `w` by `h` pixels
+pub(crate) fn resample(src: &ImageRoi, w: u32, h: u32, filter: Filter) -> Image {
+    let src_w = src.width();
+    if src_w == 0 || w == 0 || h == 0 {
+        return Image::from_color(w, h, Color::rgba(0, 0, 0, 0));
+    }
+
+    // Scale each row horizontally, then the columns of the result vertically
+    let columns = contributions(src_w, w, filter);
+    let src_rows: Vec<&[Color]> = src.rows().collect();
+    // Rows outside of the image are not returned by `rows`
+    let src_h = src_rows.len() as u32;
+    if src_h == 0 {
+        return Image::from_color(w, h, Color::rgba(0, 0, 0, 0));
+    }
+    let mut horizontal = vec![[0.0; 4]; w as usize * src_h as usize];
+    parallel::for_each_band(&mut horizontal, w as usize, |y0, band| {
+        let mut row_pixels = Vec::with_capacity(src_w as usize);
+        for (row, dst) in src_rows[y0..].iter().zip(band.chunks_mut(w as usize)) {
+            row_pixels.clear();
+            row_pixels.extend(row.iter().map(|&color| premultiply(color)));
+            for (contribution, pixel) in columns.iter().zip(dst.iter_mut()) {
+                for (weight, src) in contribution
+                    .weights
+                    .iter()
+                    .zip(&row_pixels[contribution.start..])
+                {
+                    for c in 0..4 {
+                        pixel[c] += weight * src[c];
+                    }
+                }
+            }
+        }
+    });
+
+    let rows = contributions(src_h, h, filter);
+    let mut data = vec![Color::rgba(0, 0, 0, 0); w as usize * h as usize];
+    parallel::for_each_band(&mut data, w as usize, |y0, band| {
+        for (contribution, dst) in rows[y0..].iter().zip(band.chunks_mut(w as usize)) {
+            for (x, dst) in dst.iter_mut().enumerate() {
+                let mut pixel = [0.0; 4];
+                for (i, weight) in contribution.weights.iter().enumerate() {
+                    let src = horizontal[(contribution.start + i) * w as usize + x];
+                    for c in 0..4 {
+                        pixel[c] += weight * src[c];
+                    }
+                }
+                *dst = unpremultiply(pixel);
+            }
+        }
+    });
+
+    Image::from_data(w, h, data.into_boxed_slice()).expect("resampled image has the requested size")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rect::Rect;
+    use crate::renderer::Renderer;
+
+    const FILTERS: [Filter; 4] = [
+        Filter::Nearest,
+        Filter::Bilinear,
+        Filter::Bicubic,
+        Filter::Area,
+    ];
+
+    /// Sizes to scale 7 by 5 pixels to, up, down and both
+    const SIZES: [(u32, u32); 6] = [(7, 5), (13, 3), (3, 11), (20, 20), (2, 2), (1, 1)];
+
+    fn scale(image: &Image, w: u32, h: u32, filter: Filter) -> Image {
+        let roi = image.roi(&Rect::new(0, 0, image.width(), image.height()));
+        resample(&roi, w, h, filter)
+    }
+
+    /// Random colors, all with some alpha as transparent pixels all become transparent black
+    fn noise(w: u32, h: u32) -> Image {
+        let mut state = 0x9E3779B9u32;
+        let data: Vec<Color> = (0..w * h)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                Color {
+                    data: state | 0x01000000,
+                }
+            })
+            .collect();
+        Image::from_data(w, h, data.into_boxed_slice()).unwrap()
+    }
+
+    #[test]
+    fn identity() {
+        let image = noise(7, 5);
+        for &filter in FILTERS.iter() {
+            let scaled = scale(&image, 7, 5, filter);
+            for (i, (a, b)) in scaled.data().iter().zip(image.data()).enumerate() {
+                assert_eq!(a.data, b.data, "{:?} pixel {}", filter, i);
+            }
+        }
+    }
+
+    #[test]
+    fn weights_sum_to_one() {
+        for &filter in FILTERS.iter() {
+            for &(src, dst) in &[(7, 7), (7, 13), (13, 7), (5, 1), (1, 5), (100, 3), (3, 100)] {
+                for contribution in contributions(src, dst, filter) {
+                    let sum: f32 = contribution.weights.iter().sum();
+                    assert!((sum - 1.0).abs() < 1e-5, "{:?} {} to {}", filter, src, dst);
+                    assert!(contribution.start + contribution.weights.len() <= src as usize);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn solid() {
+        let color = Color::rgba(200, 100, 50, 180);
+        let image = Image::from_color(7, 5, color);
+        for &filter in FILTERS.iter() {
+            for &(w, h) in SIZES.iter() {
+                let scaled = scale(&image, w, h, filter);
+                assert_eq!((scaled.width(), scaled.height()), (w, h));
+                assert!(
+                    scaled.data().iter().all(|pixel| pixel.data == color.data),
+                    "{:?} {}x{}",
+                    filter,
+                    w,
+                    h
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn transparent_edge() {
+        // Transparent black on the left, opaque orange on the right
+        let color = Color::rgb(255, 128, 0);
+        let mut image = Image::from_color(7, 5, Color::rgba(0, 0, 0, 0));
+        image.rect(3, 0, 4, 5, color);
+        for &filter in FILTERS.iter() {
+            for &(w, h) in SIZES.iter() {
+                let scaled = scale(&image, w, h, filter);
+                for pixel in scaled.data().iter().filter(|pixel| pixel.a() > 0) {
+                    // Mixing in the black of transparent pixels would darken the edge
+                    assert_eq!(pixel.r(), 255, "{:?} {}x{}", filter, w, h);
+                    assert!(pixel.g().abs_diff(128) <= 1, "{:?} {}x{}", filter, w, h);
+                    assert_eq!(pixel.b(), 0, "{:?} {}x{}", filter, w, h);
+                }
+                // The edge is blended, except by nearest sampling
+                if filter != Filter::Nearest && w > 7 {
+                    assert!(scaled.data().iter().any(|pixel| (1..255).contains(&pixel.a())));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,441 @@
+// SPDX-License-Identifier: MIT
+
+//! Alpha blending of runs of pixels, using SSE2 or AVX2 on x86_64 and NEON on aarch64.
+//!
+//! Every path computes exactly what the scalar code does: each color channel becomes
+//! `(old * (255 - alpha) + new * alpha) >> 8`, and alpha becomes
+//! `(old * (255 - alpha) + 256 * alpha) >> 8`.
+
+use core::cmp;
+
+use crate::color::Color;
+
+/// Blend `src` over `dst` pixel by pixel, with no shortcut for opaque or transparent pixels
+pub(crate) fn blend(dst: &mut [Color], src: &[Color]) {
+    let len = cmp::min(dst.len(), src.len());
+    blend_impl(&mut dst[..len], &src[..len]);
+}
+
+/// Blend one color over every pixel of `dst`, with no shortcut for opaque or transparent colors
+pub(crate) fn blend_color(dst: &mut [Color], color: Color) {
+    blend_color_impl(dst, color);
+}
+
+/// Draw `src` on `dst` as `Renderer::pixel` does: opaque pixels and `replace` overwrite,
+/// transparent pixels are skipped and the others are blended
+#[cfg(feature = "std")]
+pub(crate) fn draw(dst: &mut [Color], src: &[Color], replace: bool) {
+    let len = cmp::min(dst.len(), src.len());
+    let (dst, src) = (&mut dst[..len], &src[..len]);
+    if replace || src.iter().all(|color| color.a() == 255) {
+        dst.copy_from_slice(src);
+    } else if src.iter().all(|color| color.a() > 0 && color.a() < 255) {
+        blend_impl(dst, src);
+    } else {
+        for (old, new) in dst.iter_mut().zip(src) {
+            match new.a() {
+                0 => (),
+                255 => *old = *new,
+                _ => old.data = blend_pixel(old.data, new.data),
+            }
+        }
+    }
+}
+
+/// Draw one color on every pixel of `dst` as `Renderer::pixel` does
+#[cfg(feature = "std")]
+pub(crate) fn draw_color(dst: &mut [Color], color: Color, replace: bool) {
+    match color.a() {
+        _ if replace => dst.fill(color),
+        0 => (),
+        255 => dst.fill(color),
+        _ => blend_color_impl(dst, color),
+    }
+}
+
+#[inline]
+fn blend_pixel(old: u32, new: u32) -> u32 {
+    let alpha = (new >> 24) & 0xFF;
+    let n_alpha = 255 - alpha;
+
+    let rb = ((n_alpha * (old & 0x00FF00FF)) + (alpha * (new & 0x00FF00FF))) >> 8;
+    let ag =
+        (n_alpha * ((old & 0xFF00FF00) >> 8)) + (alpha * (0x01000000 | ((new & 0x0000FF00) >> 8)));
+
+    (rb & 0x00FF00FF) | (ag & 0xFF00FF00)
+}
+
+fn blend_scalar(dst: &mut [Color], src: &[Color]) {
+    for (old, new) in dst.iter_mut().zip(src) {
+        old.data = blend_pixel(old.data, new.data);
+    }
+}
+
+fn blend_color_scalar(dst: &mut [Color], color: Color) {
+    for old in dst.iter_mut() {
+        old.data = blend_pixel(old.data, color.data);
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+fn blend_impl(dst: &mut [Color], src: &[Color]) {
+    #[cfg(feature = "std")]
+    if std::is_x86_feature_detected!("avx2") {
+        // Safe as AVX2 was detected
+        unsafe { x86_64::blend_avx2(dst, src) };
+        return;
+    }
+    // Safe as SSE2 is part of x86_64
+    unsafe { x86_64::blend_sse2(dst, src) }
+}
+
+#[cfg(target_arch = "x86_64")]
+fn blend_color_impl(dst: &mut [Color], color: Color) {
+    #[cfg(feature = "std")]
+    if std::is_x86_feature_detected!("avx2") {
+        // Safe as AVX2 was detected
+        unsafe { x86_64::blend_color_avx2(dst, color) };
+        return;
+    }
+    // Safe as SSE2 is part of x86_64
+    unsafe { x86_64::blend_color_sse2(dst, color) }
+}
+
+#[cfg(all(
+    target_arch = "aarch64",
+    target_feature = "neon",
+    target_endian = "little"
+))]
+fn blend_impl(dst: &mut [Color], src: &[Color]) {
+    // Safe as the target has NEON
+    unsafe { aarch64::blend_neon(dst, src) }
+}
+
+#[cfg(all(
+    target_arch = "aarch64",
+    target_feature = "neon",
+    target_endian = "little"
+))]
+fn blend_color_impl(dst: &mut [Color], color: Color) {
+    // Safe as the target has NEON
+    unsafe { aarch64::blend_color_neon(dst, color) }
+}
+
+#[cfg(not(any(
+    target_arch = "x86_64",
+    all(
+        target_arch = "aarch64",
+        target_feature = "neon",
+        target_endian = "little"
+    )
+)))]
+use self::{blend_color_scalar as blend_color_impl, blend_scalar as blend_impl};
+
+#[cfg(target_arch = "x86_64")]
+mod x86_64 {
+    use core::arch::x86_64::*;
+
+    use super::{blend_color_scalar, blend_scalar};
+    use crate::color::Color;
+
+    /// Blend two pixels widened to 16 bits per channel
+    #[inline]
+    unsafe fn blend_wide_sse2(old: __m128i, new: __m128i) -> __m128i {
+        let alpha = _mm_shufflehi_epi16(_mm_shufflelo_epi16(new, 0xFF), 0xFF);
+        let n_alpha = _mm_sub_epi16(_mm_set1_epi16(255), alpha);
+        // Alpha is blended towards 256 rather than the new alpha
+        let new = _mm_or_si128(
+            _mm_and_si128(new, _mm_set_epi16(0, -1, -1, -1, 0, -1, -1, -1)),
+            _mm_set_epi16(256, 0, 0, 0, 256, 0, 0, 0),
+        );
+        _mm_srli_epi16(
+            _mm_add_epi16(_mm_mullo_epi16(old, n_alpha), _mm_mullo_epi16(new, alpha)),
+            8,
+        )
+    }
+
+    #[inline]
+    unsafe fn blend4_sse2(old: __m128i, new: __m128i) -> __m128i {
+        let zero = _mm_setzero_si128();
+        let lo = blend_wide_sse2(_mm_unpacklo_epi8(old, zero), _mm_unpacklo_epi8(new, zero));
+        let hi = blend_wide_sse2(_mm_unpackhi_epi8(old, zero), _mm_unpackhi_epi8(new, zero));
+        _mm_packus_epi16(lo, hi)
+    }
+
+    pub(super) unsafe fn blend_sse2(dst: &mut [Color], src: &[Color]) {
+        let mut dst_chunks = dst.chunks_exact_mut(4);
+        let mut src_chunks = src.chunks_exact(4);
+        for (old, new) in (&mut dst_chunks).zip(&mut src_chunks) {
+            let ptr = old.as_mut_ptr() as *mut __m128i;
+            let new = _mm_loadu_si128(new.as_ptr() as *const __m128i);
+            _mm_storeu_si128(ptr, blend4_sse2(_mm_loadu_si128(ptr), new));
+        }
+        blend_scalar(dst_chunks.into_remainder(), src_chunks.remainder());
+    }
+
+    pub(super) unsafe fn blend_color_sse2(dst: &mut [Color], color: Color) {
+        let new = _mm_set1_epi32(color.data as i32);
+        let mut dst_chunks = dst.chunks_exact_mut(4);
+        for old in &mut dst_chunks {
+            let ptr = old.as_mut_ptr() as *mut __m128i;
+            _mm_storeu_si128(ptr, blend4_sse2(_mm_loadu_si128(ptr), new));
+        }
+        blend_color_scalar(dst_chunks.into_remainder(), color);
+    }
+
+    /// Blend four pixels widened to 16 bits per channel
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    #[cfg(feature = "std")]
+    unsafe fn blend_wide_avx2(old: __m256i, new: __m256i) -> __m256i {
+        let alpha = _mm256_shufflehi_epi16(_mm256_shufflelo_epi16(new, 0xFF), 0xFF);
+        let n_alpha = _mm256_sub_epi16(_mm256_set1_epi16(255), alpha);
+        let new = _mm256_or_si256(
+            _mm256_and_si256(
+                new,
+                _mm256_set_epi16(0, -1, -1, -1, 0, -1, -1, -1, 0, -1, -1, -1, 0, -1, -1, -1),
+            ),
+            _mm256_set_epi16(256, 0, 0, 0, 256, 0, 0, 0, 256, 0, 0, 0, 256, 0, 0, 0),
+        );
+        _mm256_srli_epi16(
+            _mm256_add_epi16(
+                _mm256_mullo_epi16(old, n_alpha),
+                _mm256_mullo_epi16(new, alpha),
+            ),
+            8,
+        )
+    }
+
+    /// Unpacking and packing work within each 128-bit lane, so the pixels stay in order
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    #[cfg(feature = "std")]
+    unsafe fn blend8_avx2(old: __m256i, new: __m256i) -> __m256i {
+        let zero = _mm256_setzero_si256();
+        let lo = blend_wide_avx2(
+            _mm256_unpacklo_epi8(old, zero),
+            _mm256_unpacklo_epi8(new, zero),
+        );
+        let hi = blend_wide_avx2(
+            _mm256_unpackhi_epi8(old, zero),
+            _mm256_unpackhi_epi8(new, zero),
+        );
+        _mm256_packus_epi16(lo, hi)
+    }
+
+    #[cfg(feature = "std")]
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn blend_avx2(dst: &mut [Color], src: &[Color]) {
+        let mut dst_chunks = dst.chunks_exact_mut(8);
+        let mut src_chunks = src.chunks_exact(8);
+        for (old, new) in (&mut dst_chunks).zip(&mut src_chunks) {
+            let ptr = old.as_mut_ptr() as *mut __m256i;
+            let new = _mm256_loadu_si256(new.as_ptr() as *const __m256i);
+            _mm256_storeu_si256(ptr, blend8_avx2(_mm256_loadu_si256(ptr), new));
+        }
+        blend_sse2(dst_chunks.into_remainder(), src_chunks.remainder());
+    }
+
+    #[cfg(feature = "std")]
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn blend_color_avx2(dst: &mut [Color], color: Color) {
+        let new = _mm256_set1_epi32(color.data as i32);
+        let mut dst_chunks = dst.chunks_exact_mut(8);
+        for old in &mut dst_chunks {
+            let ptr = old.as_mut_ptr() as *mut __m256i;
+            _mm256_storeu_si256(ptr, blend8_avx2(_mm256_loadu_si256(ptr), new));
+        }
+        blend_color_sse2(dst_chunks.into_remainder(), color);
+    }
+}
+
+#[cfg(all(
+    target_arch = "aarch64",
+    target_feature = "neon",
+    target_endian = "little"
+))]
+mod aarch64 {
+    use core::arch::aarch64::*;
+
+    use super::{blend_color_scalar, blend_scalar};
+    use crate::color::Color;
+
+    /// Blend eight pixels split into blue, green, red and alpha vectors
+    #[inline]
+    unsafe fn blend8(old: uint8x8x4_t, new: uint8x8x4_t) -> uint8x8x4_t {
+        let alpha = new.3;
+        let n_alpha = vmvn_u8(alpha);
+        uint8x8x4_t(
+            vshrn_n_u16(vmlal_u8(vmull_u8(old.0, n_alpha), new.0, alpha), 8),
+            vshrn_n_u16(vmlal_u8(vmull_u8(old.1, n_alpha), new.1, alpha), 8),
+            vshrn_n_u16(vmlal_u8(vmull_u8(old.2, n_alpha), new.2, alpha), 8),
+            // Alpha is blended towards 256 rather than the new alpha
+            vshrn_n_u16(
+                vaddq_u16(vmull_u8(old.3, n_alpha), vshlq_n_u16(vmovl_u8(alpha), 8)),
+                8,
+            ),
+        )
+    }
+
+    pub(super) unsafe fn blend_neon(dst: &mut [Color], src: &[Color]) {
+        let mut dst_chunks = dst.chunks_exact_mut(8);
+        let mut src_chunks = src.chunks_exact(8);
+        for (old, new) in (&mut dst_chunks).zip(&mut src_chunks) {
+            let ptr = old.as_mut_ptr() as *mut u8;
+            let new = vld4_u8(new.as_ptr() as *const u8);
+            vst4_u8(ptr, blend8(vld4_u8(ptr), new));
+        }
+        blend_scalar(dst_chunks.into_remainder(), src_chunks.remainder());
+    }
+
+    pub(super) unsafe fn blend_color_neon(dst: &mut [Color], color: Color) {
+        let new = uint8x8x4_t(
+            vdup_n_u8(color.b()),
+            vdup_n_u8(color.g()),
+            vdup_n_u8(color.r()),
+            vdup_n_u8(color.a()),
+        );
+        let mut dst_chunks = dst.chunks_exact_mut(8);
+        for old in &mut dst_chunks {
+            let ptr = old.as_mut_ptr() as *mut u8;
+            vst4_u8(ptr, blend8(vld4_u8(ptr), new));
+        }
+        blend_color_scalar(dst_chunks.into_remainder(), color);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(not(feature = "std"))]
+    use alloc::{vec, vec::Vec};
+
+    use super::*;
+
+    type Blend = fn(&mut [Color], &[Color]);
+    type BlendColor = fn(&mut [Color], Color);
+
+    /// Every vector implementation compiled in and supported by this CPU
+    fn paths() -> Vec<(&'static str, Blend, BlendColor)> {
+        #[allow(unused_mut)]
+        let mut paths: Vec<(&'static str, Blend, BlendColor)> = Vec::new();
+        #[cfg(target_arch = "x86_64")]
+        {
+            // Safe as SSE2 is part of x86_64
+            paths.push((
+                "sse2",
+                |dst, src| unsafe { x86_64::blend_sse2(dst, src) },
+                |dst, color| unsafe { x86_64::blend_color_sse2(dst, color) },
+            ));
+            #[cfg(feature = "std")]
+            if std::is_x86_feature_detected!("avx2") {
+                // Safe as AVX2 was detected
+                paths.push((
+                    "avx2",
+                    |dst, src| unsafe { x86_64::blend_avx2(dst, src) },
+                    |dst, color| unsafe { x86_64::blend_color_avx2(dst, color) },
+                ));
+            }
+        }
+        #[cfg(all(
+            target_arch = "aarch64",
+            target_feature = "neon",
+            target_endian = "little"
+        ))]
+        paths.push((
+            "neon",
+            |dst, src| unsafe { aarch64::blend_neon(dst, src) },
+            |dst, color| unsafe { aarch64::blend_color_neon(dst, color) },
+        ));
+        paths
+    }
+
+    /// A xorshift generator, so that failures can be reproduced
+    struct Random(u32);
+
+    impl Random {
+        fn next(&mut self) -> u32 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 17;
+            self.0 ^= self.0 << 5;
+            self.0
+        }
+
+        /// A color, often with alpha 0 or 255
+        fn color(&mut self) -> Color {
+            let data = self.next();
+            let alpha = match self.next() % 4 {
+                0 => 0,
+                1 => 0xFF000000,
+                _ => data & 0xFF000000,
+            };
+            Color {
+                data: (data & 0x00FFFFFF) | alpha,
+            }
+        }
+    }
+
+    fn assert_same(name: &str, actual: &[Color], expected: &[Color]) {
+        for (i, (a, e)) in actual.iter().zip(expected).enumerate() {
+            assert_eq!(a.data, e.data, "{} differs at {}", name, i);
+        }
+    }
+
+    #[test]
+    fn every_alpha_and_old_value() {
+        // Every old value in every channel under every alpha, with new values from both ends
+        // and across the range
+        let values = (0..=255u32).step_by(15).chain([1, 127, 128, 254]);
+        let old: Vec<Color> = (0..=255u32)
+            .map(|v| Color {
+                data: v * 0x01010101,
+            })
+            .collect();
+        for (name, blend, blend_color) in paths() {
+            for alpha in 0..=255u32 {
+                for value in values.clone() {
+                    let new = Color {
+                        data: (alpha << 24) | (value * 0x010101),
+                    };
+                    let src = vec![new; old.len()];
+                    let mut expected = old.clone();
+                    blend_scalar(&mut expected, &src);
+
+                    let mut actual = old.clone();
+                    blend(&mut actual, &src);
+                    assert_same(name, &actual, &expected);
+
+                    let mut actual = old.clone();
+                    blend_color(&mut actual, new);
+                    assert_same(name, &actual, &expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn random_lengths_and_offsets() {
+        let mut random = Random(0x2545F491);
+        for (name, blend, blend_color) in paths() {
+            for len in 0..40 {
+                // Slices starting off the vector alignment, with tails of every length
+                for offset in 0..4 {
+                    let old: Vec<Color> = (0..offset + len).map(|_| random.color()).collect();
+                    let src: Vec<Color> = (0..offset + len).map(|_| random.color()).collect();
+                    let color = random.color();
+
+                    let mut expected = old.clone();
+                    blend_scalar(&mut expected[offset..], &src[offset..]);
+                    let mut actual = old.clone();
+                    blend(&mut actual[offset..], &src[offset..]);
+                    assert_same(name, &actual, &expected);
+
+                    let mut expected = old.clone();
+                    blend_color_scalar(&mut expected[offset..], color);
+                    let mut actual = old.clone();
+                    blend_color(&mut actual[offset..], color);
+                    assert_same(name, &actual, &expected);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,616 @@
+// SPDX-License-Identifier: MIT
+
+//! Drawing at fractional coordinates, with edges anti-aliased by the area of each pixel they
+//! cover, so that shapes moving by less than a pixel move smoothly.
+
+#[cfg(not(feature = "std"))]
+use alloc::{vec, vec::Vec};
+
+use core::cmp;
+
+use crate::color::Color;
+use crate::image::ImageRoi;
+use crate::math;
+use crate::rect::Rect;
+use crate::renderer::Renderer;
+use crate::resample::{self, Filter};
+
+/// Largest distance in pixels between a curve and the lines drawn for it
+#[cfg(feature = "std")]
+const TOLERANCE: f32 = 0.1;
+
+/// A path with fractional coordinates, made of subpaths that are each open or closed
+#[derive(Clone, Debug, Default)]
+pub struct Path {
+    pub(crate) points: Vec<[f32; 2]>,
+    /// Index of the first point and whether it is closed, for each subpath
+    subpaths: Vec<(usize, bool)>,
+}
+
+impl Path {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start a new subpath
+    pub fn move_to(&mut self, x: f32, y: f32) {
+        self.subpaths.push((self.points.len(), false));
+        self.points.push([x, y]);
+    }
+
+    /// Add a line from the last point, starting a subpath at the point if there is none
+    pub fn line_to(&mut self, x: f32, y: f32) {
+        match self.subpaths.last() {
+            Some(&(_, false)) => self.points.push([x, y]),
+            _ => self.move_to(x, y),
+        }
+    }
+
+    /// Add a quadratic bezier curve from the last point
+    pub fn quadratic_curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32) {
+        let [x0, y0] = self.last();
+        let steps = curve_steps(&[[x0, y0], [x1, y1], [x2, y2]]);
+        for i in 1..=steps {
+            let t = i as f32 / steps as f32;
+            let u = 1.0 - t;
+            self.line_to(
+                u * u * x0 + 2.0 * u * t * x1 + t * t * x2,
+                u * u * y0 + 2.0 * u * t * y1 + t * t * y2,
+            );
+        }
+    }
+
+    /// Add a cubic bezier curve from the last point
+    pub fn bezier_curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x3: f32, y3: f32) {
+        let [x0, y0] = self.last();
+        let steps = curve_steps(&[[x0, y0], [x1, y1], [x2, y2], [x3, y3]]);
+        for i in 1..=steps {
+            let t = i as f32 / steps as f32;
+            let u = 1.0 - t;
+            let (a, b, c, d) = (u * u * u, 3.0 * u * u * t, 3.0 * u * t * t, t * t * t);
+            self.line_to(
+                a * x0 + b * x1 + c * x2 + d * x3,
+                a * y0 + b * y1 + c * y2 + d * y3,
+            );
+        }
+    }
+
+    /// Close the current subpath with a line back to its first point. The next point starts
+    /// a new subpath.
+    pub fn close(&mut self) {
+        if let Some(subpath) = self.subpaths.last_mut() {
+            subpath.1 = true;
+        }
+    }
+
+    fn last(&self) -> [f32; 2] {
+        self.points.last().copied().unwrap_or([0.0, 0.0])
+    }
+
+    /// The points of each subpath and whether it is closed
+    fn subpaths(&self) -> impl Iterator<Item = (&[[f32; 2]], bool)> + '_ {
+        self.subpaths
+            .iter()
+            .enumerate()
+            .map(move |(i, &(start, closed))| {
+                let end = self
+                    .subpaths
+                    .get(i + 1)
+                    .map_or(self.points.len(), |next| next.0);
+                (&self.points[start..end], closed)
+            })
+    }
+}
+
+/// Lines to split a curve into, so that each is at most about a pixel long
+fn curve_steps(points: &[[f32; 2]]) -> usize {
+    let length: f32 = points
+        .windows(2)
+        .map(|pair| (pair[1][0] - pair[0][0]).abs() + (pair[1][1] - pair[0][1]).abs())
+        .sum();
+    (math::ceil(length) as usize).clamp(1, 256)
+}
+
+/// Coverage of pixels by polygons, accumulated exactly from the area to the right of each
+/// edge within each pixel. Overlapping polygons with the same winding cover a pixel once,
+/// and polygons with opposite windings cut holes.
+pub(crate) struct Coverage {
+    bounds: Rect,
+    stride: usize,
+    area: Vec<f32>,
+}
+
+impl Coverage {
+    /// Coverage of the pixels in `bounds`, clipped to the renderer
+    pub(crate) fn new<R: Renderer + ?Sized>(renderer: &R, bounds: Rect) -> Self {
+        let bounds = bounds.intersection(&Rect::new(0, 0, renderer.width(), renderer.height()));
+        // One cell past each side of a row for edges at or beyond the right of the bounds
+        let stride = bounds.width() as usize + 2;
+        Coverage {
+            bounds,
+            stride,
+            area: vec![0.0; stride * bounds.height() as usize],
+        }
+    }
+
+    /// Coverage of the pixels touched by points, clipped to the renderer
+    pub(crate) fn around<R: Renderer + ?Sized>(renderer: &R, points: &[[f32; 2]]) -> Self {
+        let mut min = [f32::MAX; 2];
+        let mut max = [f32::MIN; 2];
+        for point in points {
+            for c in 0..2 {
+                min[c] = min[c].min(point[c]);
+                max[c] = max[c].max(point[c]);
+            }
+        }
+        let bounds = if points.is_empty() {
+            Rect::default()
+        } else {
+            let (left, top) = (math::floor(min[0]), math::floor(min[1]));
+            Rect::new(
+                left,
+                top,
+                math::ceil(max[0]).saturating_sub(left) as u32,
+                math::ceil(max[1]).saturating_sub(top) as u32,
+            )
+        };
+        Self::new(renderer, bounds)
+    }
+
+    /// Add a polygon, closing it from the last point to the first
+    pub(crate) fn polygon(&mut self, points: &[[f32; 2]]) {
+        for (i, &point) in points.iter().enumerate() {
+            self.edge(point, points[(i + 1) % points.len()]);
+        }
+    }
+
+    fn edge(&mut self, from: [f32; 2], to: [f32; 2]) {
+        if self.bounds.is_empty() {
+            return;
+        }
+        let w = self.bounds.width() as f32;
+        let (left, top) = (self.bounds.left() as f32, self.bounds.top() as f32);
+        let (x0, y0) = (from[0] - left, from[1] - top);
+        let (x1, y1) = (to[0] - left, to[1] - top);
+
+        // Split the edge where it crosses the sides, then move the parts outside onto them,
+        // where they cover everything to their right or nothing
+        let mut splits = [0.0, 1.0, 1.0, 1.0];
+        if x0 != x1 {
+            splits[1] = ((0.0 - x0) / (x1 - x0)).clamp(0.0, 1.0);
+            splits[2] = ((w - x0) / (x1 - x0)).clamp(0.0, 1.0);
+            splits[1..3].sort_by(f32::total_cmp);
+        }
+        let at = |t: f32| [(x0 + (x1 - x0) * t).clamp(0.0, w), y0 + (y1 - y0) * t];
+        for pair in splits.windows(2) {
+            if pair[1] > pair[0] {
+                self.accumulate(at(pair[0]), at(pair[1]));
+            }
+        }
+    }
+
+    /// Accumulate an edge in local coordinates, with x between 0 and the width
+    fn accumulate(&mut self, from: [f32; 2], to: [f32; 2]) {
+        if from[1] == to[1] {
+            return;
+        }
+        let (direction, [x0, y0], [x1, y1]) = if from[1] < to[1] {
+            (1.0, from, to)
+        } else {
+            (-1.0, to, from)
+        };
+        let dxdy = (x1 - x0) / (y1 - y0);
+        let first = cmp::max(math::floor(y0), 0);
+        let last = cmp::min(math::ceil(y1), self.bounds.height() as i32);
+        for y in first..last {
+            let row_top = (y as f32).max(y0);
+            let row_bottom = ((y + 1) as f32).min(y1);
+            let d = (row_bottom - row_top) * direction;
+            let xa = x0 + (row_top - y0) * dxdy;
+            let xb = x0 + (row_bottom - y0) * dxdy;
+            let start = y as usize * self.stride;
+            let row = &mut self.area[start..start + self.stride];
+
+            let (left, right) = if xa < xb { (xa, xb) } else { (xb, xa) };
+            let left_floor = math::floor(left);
+            let right_ceil = math::ceil(right);
+            let l = left_floor as usize;
+            if right_ceil <= left_floor + 1 {
+                // Within one pixel, split at the middle of the edge
+                let middle = 0.5 * (xa + xb) - left_floor as f32;
+                row[l] += d * (1.0 - middle);
+                row[l + 1] += d * middle;
+            } else {
+                // Across pixels, the area right of the edge grows as a triangle in the first,
+                // linearly in the middle and as a triangle in the last
+                let r = right_ceil as usize;
+                let s = 1.0 / (right - left);
+                let left_fraction = left - left_floor as f32;
+                let first_area = 0.5 * s * (1.0 - left_fraction) * (1.0 - left_fraction);
+                let right_fraction = right - right_ceil as f32 + 1.0;
+                let last_area = 0.5 * s * right_fraction * right_fraction;
+                row[l] += d * first_area;
+                if r == l + 2 {
+                    row[l + 1] += d * (1.0 - first_area - last_area);
+                } else {
+                    let second_area = s * (1.5 - left_fraction);
+                    row[l + 1] += d * (second_area - first_area);
+                    for cell in row[l + 2..r - 1].iter_mut() {
+                        *cell += d * s;
+                    }
+                    let before_last = second_area + (r - l - 3) as f32 * s;
+                    row[r - 1] += d * (1.0 - before_last - last_area);
+                }
+                row[r] += d * last_area;
+            }
+        }
+    }
+
+    /// Draw `color` with its alpha scaled by the coverage of each pixel
+    pub(crate) fn draw<R: Renderer + ?Sized>(&self, renderer: &mut R, color: Color) {
+        let w = self.bounds.width() as usize;
+        for (y, row) in self.area.chunks_exact(self.stride).enumerate() {
+            let y = self.bounds.top() + y as i32;
+            let mut sum = 0.0;
+            let mut run = 0;
+            for (x, cell) in row[..w].iter().enumerate() {
+                sum += cell;
+                let pixel = with_coverage(color, sum.abs());
+                let x = self.bounds.left() + x as i32;
+                // Fully covered runs are filled at once
+                if pixel.data == color.data {
+                    run += 1;
+                    continue;
+                }
+                if run > 0 {
+                    renderer.rect(x - run, y, run as u32, 1, color);
+                    run = 0;
+                }
+                if pixel.a() > 0 {
+                    renderer.pixel(x, y, pixel);
+                }
+            }
+            if run > 0 {
+                renderer.rect(self.bounds.right() - run, y, run as u32, 1, color);
+            }
+        }
+    }
+}
+
+/// `color` with its alpha scaled by the covered fraction of a pixel
+fn with_coverage(color: Color, coverage: f32) -> Color {
+    if coverage >= 1.0 {
+        return color;
+    }
+    let alpha = math::round(color.a() as f32 * coverage) as u8;
+    Color::rgba(color.r(), color.g(), color.b(), alpha)
+}
+
+/// The part of the pixel at `pixel` covered by the span from `start` to `end`
+fn overlap(pixel: i32, start: f32, end: f32) -> f32 {
+    let pixel = pixel as f32;
+    (end.min(pixel + 1.0) - start.max(pixel)).max(0.0)
+}
+
+/// Rows or columns of the pixels touched by a span, clipped to `0..size`
+fn span_pixels(start: f32, end: f32, size: u32) -> core::ops::Range<i32> {
+    cmp::max(math::floor(start), 0)..cmp::min(math::ceil(end), size as i32)
+}
+
+pub(crate) fn fill_rect<R: Renderer + ?Sized>(
+    renderer: &mut R,
+    x: f32,
+    y: f32,
+    w: f32,
+    h: f32,
+    color: Color,
+) {
+    if w <= 0.0 || h <= 0.0 {
+        return;
+    }
+    let (right, bottom) = (x + w, y + h);
+    let (first, last) = (math::floor(x), math::ceil(right) - 1);
+    for row in span_pixels(y, bottom, renderer.height()) {
+        let row_color = with_coverage(color, overlap(row, y, bottom));
+        if first == last {
+            renderer.pixel(first, row, with_coverage(row_color, w));
+            continue;
+        }
+        renderer.pixel(
+            first,
+            row,
+            with_coverage(row_color, overlap(first, x, right)),
+        );
+        if last > first + 1 {
+            // Clip before converting, as the rect may be far wider than the renderer
+            let inner_left = cmp::max(first + 1, 0);
+            let inner_right = cmp::min(last, renderer.width() as i32);
+            if inner_right > inner_left {
+                renderer.rect(
+                    inner_left,
+                    row,
+                    (inner_right - inner_left) as u32,
+                    1,
+                    row_color,
+                );
+            }
+        }
+        renderer.pixel(last, row, with_coverage(row_color, overlap(last, x, right)));
+    }
+}
+
+/// Fill the inside of a path with the nonzero winding rule, closing every subpath
+pub(crate) fn fill_path<R: Renderer + ?Sized>(renderer: &mut R, path: &Path, color: Color) {
+    let mut coverage = Coverage::around(renderer, &path.points);
+    for (points, _) in path.subpaths() {
+        coverage.polygon(points);
+    }
+    coverage.draw(renderer, color);
+}
+
+/// Draw a line with square ends at the points
+#[cfg(feature = "std")]
+pub(crate) fn line<R: Renderer + ?Sized>(
+    renderer: &mut R,
+    from: [f32; 2],
+    to: [f32; 2],
+    width: f32,
+    color: Color,
+) {
+    let half = width / 2.0;
+    let mut coverage = Coverage::around(
+        renderer,
+        &[
+            [from[0] - half, from[1] - half],
+            [to[0] + half, to[1] + half],
+            [from[0] + half, from[1] + half],
+            [to[0] - half, to[1] - half],
+        ],
+    );
+    if let Some(quad) = segment(from, to, half) {
+        coverage.polygon(&quad);
+    }
+    coverage.draw(renderer, color);
+}
+
+/// Draw lines joined with round corners along each subpath
+#[cfg(feature = "std")]
+pub(crate) fn stroke_path<R: Renderer + ?Sized>(
+    renderer: &mut R,
+    path: &Path,
+    width: f32,
+    color: Color,
+) {
+    let half = width / 2.0;
+    if half <= 0.0 {
+        return;
+    }
+    let mut bounds = Vec::with_capacity(path.points.len() * 2);
+    for point in path.points.iter() {
+        bounds.push([point[0] - half, point[1] - half]);
+        bounds.push([point[0] + half, point[1] + half]);
+    }
+    let mut coverage = Coverage::around(renderer, &bounds);
+    for (points, closed) in path.subpaths() {
+        let segments = if closed {
+            points.len()
+        } else {
+            points.len().saturating_sub(1)
+        };
+        for i in 0..segments {
+            let (from, to) = (points[i], points[(i + 1) % points.len()]);
+            if let Some(quad) = segment(from, to, half) {
+                coverage.polygon(&quad);
+            }
+            // Round joins, which are also the only mark of a closed single point
+            if closed || i + 1 < segments {
+                coverage.polygon(&circle(to, half, false));
+            }
+        }
+    }
+    coverage.draw(renderer, color);
+}
+
+/// The corners of a line with half its width on each side, wound like `circle(.., false)`
+#[cfg(feature = "std")]
+fn segment(from: [f32; 2], to: [f32; 2], half: f32) -> Option<[[f32; 2]; 4]> {
+    let (dx, dy) = (to[0] - from[0], to[1] - from[1]);
+    let length = (dx * dx + dy * dy).sqrt();
+    if length == 0.0 || half <= 0.0 {
+        return None;
+    }
+    let (nx, ny) = (-dy / length * half, dx / length * half);
+    Some([
+        [from[0] + nx, from[1] + ny],
+        [to[0] + nx, to[1] + ny],
+        [to[0] - nx, to[1] - ny],
+        [from[0] - nx, from[1] - ny],
+    ])
+}
+
+/// Points around a circle, close enough for the lines between them to look round
+#[cfg(feature = "std")]
+fn circle(center: [f32; 2], radius: f32, clockwise: bool) -> Vec<[f32; 2]> {
+    let steps = if radius > TOLERANCE {
+        (core::f32::consts::PI / (1.0 - TOLERANCE / radius).acos()).ceil() as usize
+    } else {
+        4
+    };
+    let steps = steps.clamp(4, 4096);
+    let step = core::f32::consts::TAU / steps as f32;
+    // Move the points out so that the polygon has the area of the circle
+    let radius = radius * (step / step.sin()).sqrt();
+    let direction = if clockwise { 1.0 } else { -1.0 };
+    (0..steps)
+        .map(|i| {
+            let angle = direction * i as f32 * step;
+            let (sin, cos) = angle.sin_cos();
+            [center[0] + radius * cos, center[1] + radius * sin]
+        })
+        .collect()
+}
+
+/// Fill a circle, or the ring between two circles when `inner` is above zero
+#[cfg(feature = "std")]
+pub(crate) fn circle_ring<R: Renderer + ?Sized>(
+    renderer: &mut R,
+    center: [f32; 2],
+    outer: f32,
+    inner: f32,
+    color: Color,
+) {
+    if outer <= 0.0 {
+        return;
+    }
+    let mut coverage = Coverage::around(
+        renderer,
+        &[
+            [center[0] - outer, center[1] - outer],
+            [center[0] + outer, center[1] + outer],
+        ],
+    );
+    coverage.polygon(&circle(center, outer, true));
+    if inner > 0.0 {
+        coverage.polygon(&circle(center, inner, false));
+    }
+    coverage.draw(renderer, color);
+}
+
+/// Draw an image region scaled to a rect with fractional coordinates, the pixels on its edges
+/// blended by how much of them it covers
+pub(crate) fn image<R: Renderer + ?Sized>(
+    renderer: &mut R,
+    src: &ImageRoi,
+    x: f32,
+    y: f32,
+    w: f32,
+    h: f32,
+    filter: Filter,
+) {
+    if src.width() == 0 || src.height() == 0 || w <= 0.0 || h <= 0.0 {
+        return;
+    }
+    let scale_x = src.width() as f32 / w;
+    let scale_y = src.height() as f32 / h;
+    for row in span_pixels(y, y + h, renderer.height()) {
+        let row_coverage = overlap(row, y, y + h);
+        let v = (row as f32 + 0.5 - y) * scale_y;
+        for column in span_pixels(x, x + w, renderer.width()) {
+            let u = (column as f32 + 0.5 - x) * scale_x;
+            let color = resample::sample(src, u, v, filter);
+            let coverage = row_coverage * overlap(column, x, x + w);
+            renderer.pixel(column, row, with_coverage(color, coverage));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::Image;
+    use crate::Mode;
+
+    const WHITE: Color = Color::rgb(255, 255, 255);
+
+    fn canvas() -> Image {
+        let image = Image::from_color(12, 12, Color::rgba(0, 0, 0, 0));
+        image.mode().set(Mode::Overwrite);
+        image
+    }
+
+    fn alphas(image: &Image) -> Vec<u8> {
+        image.data().iter().map(|pixel| pixel.a()).collect()
+    }
+
+    fn rect_path(x: f32, y: f32, w: f32, h: f32) -> Path {
+        let mut path = Path::new();
+        path.move_to(x, y);
+        path.line_to(x + w, y);
+        path.line_to(x + w, y + h);
+        path.line_to(x, y + h);
+        path.close();
+        path
+    }
+
+    #[test]
+    fn half_pixel_rect() {
+        let mut image = canvas();
+        fill_rect(&mut image, 0.5, 1.0, 4.0, 2.0, WHITE);
+        for y in 0..12 {
+            for x in 0..12 {
+                let expected = match (x, y) {
+                    (0 | 4, 1 | 2) => 128,
+                    (1..=3, 1 | 2) => 255,
+                    _ => 0,
+                };
+                assert_eq!(image.getpixel(x, y).a(), expected, "({}, {})", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn path_matches_rect() {
+        for &(x, y, w, h) in &[
+            (0.5, 1.0, 4.0, 2.0),
+            (1.25, 0.75, 3.5, 2.25),
+            (2.1, 3.6, 0.7, 5.3),
+            (-1.5, -0.5, 20.0, 3.25),
+        ] {
+            let mut rect = canvas();
+            fill_rect(&mut rect, x, y, w, h, WHITE);
+            let mut path = canvas();
+            fill_path(&mut path, &rect_path(x, y, w, h), WHITE);
+            for (i, (a, b)) in alphas(&rect).iter().zip(alphas(&path)).enumerate() {
+                assert!(a.abs_diff(b) <= 1, "{:?} pixel {}: {} {}", (x, y, w, h), i, a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn ring() {
+        // The inner square goes the other way round, cutting a hole
+        let mut path = rect_path(1.0, 1.0, 10.0, 10.0);
+        path.move_to(3.5, 3.5);
+        path.line_to(3.5, 8.5);
+        path.line_to(8.5, 8.5);
+        path.line_to(8.5, 3.5);
+        path.close();
+        let mut image = canvas();
+        fill_path(&mut image, &path, WHITE);
+        assert_eq!(image.getpixel(0, 0).a(), 0);
+        assert_eq!(image.getpixel(2, 2).a(), 255);
+        assert_eq!(image.getpixel(3, 6).a(), 128);
+        assert_eq!(image.getpixel(3, 3).a(), 191);
+        assert_eq!(image.getpixel(4, 4).a(), 0);
+        assert_eq!(image.getpixel(7, 7).a(), 0);
+        assert_eq!(image.getpixel(8, 6).a(), 128);
+        assert_eq!(image.getpixel(10, 10).a(), 255);
+
+        // With the same winding, the inner square is covered once
+        let mut path = rect_path(1.0, 1.0, 10.0, 10.0);
+        let inner = rect_path(3.5, 3.5, 5.0, 5.0);
+        path.points.extend_from_slice(&inner.points);
+        path.subpaths.push((4, true));
+        let mut image = canvas();
+        fill_path(&mut image, &path, WHITE);
+        assert!(alphas(&image)
+            .chunks(12)
+            .skip(1)
+            .take(10)
+            .all(|row| row[1..11].iter().all(|&a| a == 255)));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn circle_ring_hole() {
+        let mut image = canvas();
+        circle_ring(&mut image, [6.0, 6.0], 5.5, 2.5, WHITE);
+        assert_eq!(image.getpixel(0, 0).a(), 0);
+        assert_eq!(image.getpixel(5, 5).a(), 0);
+        assert_eq!(image.getpixel(6, 1).a(), 255);
+        assert_eq!(image.getpixel(1, 6).a(), 255);
+        let total: u32 = alphas(&image).iter().map(|&a| a as u32).sum();
+        let area = core::f32::consts::PI * (5.5 * 5.5 - 2.5 * 2.5);
+        assert!((total as f32 / 255.0 - area).abs() < 0.5, "{}", total);
+    }
+}
@@ -0,0 +1,567 @@
+// SPDX-License-Identifier: MIT
+
+//! Styled drawing of the embedded Unifont bitmaps.
+
+#[cfg(not(feature = "std"))]
+use alloc::{boxed::Box, vec, vec::Vec};
+use core::cmp;
+
+use crate::color::Color;
+use crate::font::{self, Glyph, GLYPH_HEIGHT, GLYPH_WIDTH};
+use crate::math;
+use crate::rect::Rect;
+use crate::renderer::Renderer;
+
+/// How far oblique glyphs lean to the right, in pixels per pixel of height
+const OBLIQUE_SLANT: f32 = 0.2;
+
+/// Options for drawing text with [`Renderer::text`] and [`Renderer::char_styled`]
+#[derive(Clone, Copy, Debug)]
+pub struct TextStyle {
+    /// Size of the glyphs relative to the 8x16 font, may be fractional
+    pub scale: f32,
+    /// Interpolate the edges of scaled glyphs instead of repeating pixels
+    pub smooth: bool,
+    /// Thicken the glyphs
+    pub bold: bool,
+    /// Slant the glyphs to the right
+    pub oblique: bool,
+    /// Draw a line under the text
+    pub underline: bool,
+    /// Draw a line through the text
+    pub strikethrough: bool,
+    /// Draw a 1px border of this color around the glyphs
+    pub outline: Option<Color>,
+    /// Draw a copy of the glyphs at this (x, y) offset and color behind them
+    pub shadow: Option<(i32, i32, Color)>,
+}
+
+impl TextStyle {
+    /// Plain text at 1x, as drawn by [`Renderer::char`]
+    pub const fn new() -> Self {
+        TextStyle {
+            scale: 1.0,
+            smooth: false,
+            bold: false,
+            oblique: false,
+            underline: false,
+            strikethrough: false,
+            outline: None,
+            shadow: None,
+        }
+    }
+
+    /// Plain text at the given scale
+    pub const fn scaled(scale: f32) -> Self {
+        TextStyle {
+            scale,
+            ..Self::new()
+        }
+    }
+
+    /// Horizontal distance in pixels between two characters
+    pub fn advance(&self) -> u32 {
+        self.glyph_width() + self.bold_width()
+    }
+
+    /// Vertical distance in pixels between two lines
+    pub fn line_height(&self) -> u32 {
+        math::ceil(GLYPH_HEIGHT as f32 * self.scale()).max(1) as u32
+    }
+
+    fn scale(&self) -> f32 {
+        if self.scale > 0.0 {
+            self.scale
+        } else {
+            1.0
+        }
+    }
+
+    fn glyph_width(&self) -> u32 {
+        math::ceil(GLYPH_WIDTH as f32 * self.scale()).max(1) as u32
+    }
+
+    fn bold_width(&self) -> u32 {
+        if self.bold {
+            math::round(self.scale()).max(1) as u32
+        } else {
+            0
+        }
+    }
+
+    fn line_thickness(&self) -> u32 {
+        math::round(self.scale()).max(1) as u32
+    }
+}
+
+impl Default for TextStyle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A rasterized glyph, as produced by a [`Font`]
+#[derive(Clone, Debug)]
+pub struct RasterGlyph {
+    pub width: u32,
+    pub height: u32,
+    /// Horizontal position of the glyph relative to the pen
+    pub left: i32,
+    /// Vertical position of the glyph relative to the top of the line
+    pub top: i32,
+    /// Distance to move the pen to the right after this glyph
+    pub advance: u32,
+    /// Coverage of the pixels row by row, 0 is transparent and 255 fully covered
+    pub coverage: Vec<u8>,
+}
+
+/// A font that text can be drawn with, see [`Renderer::text_font`]
+pub trait Font {
+    /// Rasterize a character to fit a line of `style.line_height()` pixels,
+    /// applying `scale`, `smooth`, `bold` and `oblique` from the style.
+    /// Returns `None` if the font has no glyph for the character.
+    fn rasterize(&self, c: char, style: &TextStyle) -> Option<RasterGlyph>;
+}
+
+/// The embedded Unifont bitmaps as a [`Font`]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Unifont;
+
+impl Font for Unifont {
+    fn rasterize(&self, c: char, style: &TextStyle) -> Option<RasterGlyph> {
+        let glyph = font::glyph(c)?;
+        let mask = rasterize(glyph, style);
+        Some(RasterGlyph {
+            width: mask.w,
+            height: mask.h,
+            left: 0,
+            top: 0,
+            advance: style.advance(),
+            coverage: mask.data,
+        })
+    }
+}
+
+/// Fonts tried in order until one has a glyph for a character.
+/// Push [`Unifont`] last to cover as many characters as possible.
+#[derive(Default)]
+pub struct FontChain {
+    fonts: Vec<Box<dyn Font>>,
+}
+
+impl FontChain {
+    /// An empty chain
+    pub fn new() -> Self {
+        FontChain { fonts: Vec::new() }
+    }
+
+    /// Add a font to try after the fonts already in the chain
+    pub fn push<F: Font + 'static>(&mut self, font: F) {
+        self.fonts.push(Box::new(font));
+    }
+
+    /// Add a font to try after the fonts already in the chain, returning the chain
+    pub fn with<F: Font + 'static>(mut self, font: F) -> Self {
+        self.push(font);
+        self
+    }
+}
+
+impl Font for FontChain {
+    fn rasterize(&self, c: char, style: &TextStyle) -> Option<RasterGlyph> {
+        self.fonts.iter().find_map(|font| font.rasterize(c, style))
+    }
+}
+
+/// A coverage map, 0 is transparent and 255 fully covered
+pub(crate) struct Mask {
+    pub(crate) w: u32,
+    pub(crate) h: u32,
+    pub(crate) data: Vec<u8>,
+}
+
+impl Mask {
+    fn new(w: u32, h: u32) -> Self {
+        Mask {
+            w,
+            h,
+            data: vec![0; w as usize * h as usize],
+        }
+    }
+
+    fn get(&self, x: i32, y: i32) -> u8 {
+        if x < 0 || y < 0 || x >= self.w as i32 || y >= self.h as i32 {
+            return 0;
+        }
+        self.data[y as usize * self.w as usize + x as usize]
+    }
+
+    /// Grow the covered area by one pixel in every direction
+    fn dilate(&self) -> Mask {
+        let mut out = Mask::new(self.w + 2, self.h + 2);
+        for y in 0..out.h as i32 {
+            for x in 0..out.w as i32 {
+                let mut value = 0;
+                for dy in -2..=0 {
+                    for dx in -2..=0 {
+                        value = value.max(self.get(x + dx, y + dy));
+                    }
+                }
+                out.data[y as usize * out.w as usize + x as usize] = value;
+            }
+        }
+        out
+    }
+
+    /// Cut off the columns right of `w`
+    pub(crate) fn crop_width(&self, w: u32) -> Mask {
+        let w = cmp::min(w, self.w);
+        let mut out = Mask::new(w, self.h);
+        if w == 0 {
+            return out;
+        }
+        for (out_row, row) in out
+            .data
+            .chunks_exact_mut(w as usize)
+            .zip(self.data.chunks_exact(self.w as usize))
+        {
+            out_row.copy_from_slice(&row[..w as usize]);
+        }
+        out
+    }
+
+    /// Blend the mask onto a renderer, scaling the alpha of `color` by the coverage
+    pub(crate) fn draw<R: Renderer + ?Sized>(
+        &self,
+        renderer: &mut R,
+        x: i32,
+        y: i32,
+        color: Color,
+    ) {
+        let alpha = color.a() as u32;
+        for my in 0..self.h as i32 {
+            for mx in 0..self.w as i32 {
+                let coverage = self.get(mx, my) as u32;
+                if coverage > 0 {
+                    let a = (alpha * coverage / 255) as u8;
+                    renderer.pixel(
+                        x + mx,
+                        y + my,
+                        Color::rgba(color.r(), color.g(), color.b(), a),
+                    );
+                }
+            }
+        }
+    }
+}
+
+fn glyph_bit(glyph: &Glyph, x: i32, y: i32) -> f32 {
+    if x < 0 || y < 0 || x >= GLYPH_WIDTH as i32 || y >= GLYPH_HEIGHT as i32 {
+        return 0.0;
+    }
+    ((glyph[y as usize] >> (7 - x)) & 1) as f32
+}
+
+/// Coverage of the glyph at a position given in scaled pixels
+fn sample(glyph: &Glyph, style: &TextStyle, x: f32, y: f32) -> f32 {
+    let scale = style.scale();
+    if style.smooth {
+        let u = (x + 0.5) / scale - 0.5;
+        let v = (y + 0.5) / scale - 0.5;
+        let (x0, y0) = (math::floor(u), math::floor(v));
+        let (tx, ty) = (u - x0 as f32, v - y0 as f32);
+        let top = glyph_bit(glyph, x0, y0) * (1.0 - tx) + glyph_bit(glyph, x0 + 1, y0) * tx;
+        let bottom =
+            glyph_bit(glyph, x0, y0 + 1) * (1.0 - tx) + glyph_bit(glyph, x0 + 1, y0 + 1) * tx;
+        top * (1.0 - ty) + bottom * ty
+    } else {
+        glyph_bit(
+            glyph,
+            math::floor((x + 0.5) / scale),
+            math::floor((y + 0.5) / scale),
+        )
+    }
+}
+
+/// Rasterize a glyph with the effects that change its shape
+pub(crate) fn rasterize(glyph: &Glyph, style: &TextStyle) -> Mask {
+    let advance = style.advance();
+    let height = style.line_height();
+    let slant = if style.oblique {
+        math::ceil(height as f32 * OBLIQUE_SLANT) as u32
+    } else {
+        0
+    };
+
+    let mut mask = Mask::new(advance + slant, height);
+    for y in 0..height {
+        let shift = if style.oblique {
+            let shift = (height - 1 - y) as f32 * OBLIQUE_SLANT;
+            if style.smooth {
+                shift
+            } else {
+                math::floor(shift) as f32
+            }
+        } else {
+            0.0
+        };
+
+        for x in 0..mask.w {
+            let fx = x as f32 - shift;
+            let mut coverage = sample(glyph, style, fx, y as f32);
+            for b in 1..=style.bold_width() {
+                coverage = coverage.max(sample(glyph, style, fx - b as f32, y as f32));
+            }
+            mask.data[(y * mask.w + x) as usize] = (coverage * 255.0 + 0.5) as u8;
+        }
+    }
+
+    mask
+}
+
+/// Draw rasterized glyphs in three passes so outlines and shadows never cover neighbouring
+/// glyphs, returning the bounds of the pixels drawn
+pub(crate) fn draw_masks<R: Renderer + ?Sized>(
+    renderer: &mut R,
+    masks: &[(i32, i32, Mask)],
+    color: Color,
+    style: &TextStyle,
+) -> Rect {
+    let Some(glyphs) = masks
+        .iter()
+        .map(|(x, y, mask)| Rect::new(*x, *y, mask.w, mask.h))
+        .filter(|rect| !rect.is_empty())
+        .reduce(|a, b| a.container(&b))
+    else {
+        return Rect::default();
+    };
+    let mut bounds = glyphs;
+
+    if let Some((dx, dy, shadow)) = style.shadow {
+        bounds = bounds.container(&glyphs.translate(dx, dy));
+        for (x, y, mask) in masks {
+            mask.draw(renderer, x + dx, y + dy, shadow);
+        }
+    }
+    if let Some(outline) = style.outline {
+        let dilated = Rect::new(
+            glyphs.left() - 1,
+            glyphs.top() - 1,
+            glyphs.width() + 2,
+            glyphs.height() + 2,
+        );
+        bounds = bounds.container(&dilated);
+        for (x, y, mask) in masks {
+            mask.dilate().draw(renderer, x - 1, y - 1, outline);
+        }
+    }
+    for (x, y, mask) in masks {
+        mask.draw(renderer, *x, *y, color);
+    }
+    bounds
+}
+
+/// Check if a character is drawn on top of the preceding one instead of in its own cell
+pub fn is_combining(c: char) -> bool {
+    matches!(c as u32,
+        0x0300..=0x036F // Combining Diacritical Marks
+        | 0x0483..=0x0489 // Cyrillic combining marks
+        | 0x0591..=0x05BD | 0x05BF | 0x05C1..=0x05C2 | 0x05C4..=0x05C5 | 0x05C7 // Hebrew points
+        | 0x0610..=0x061A | 0x064B..=0x065F | 0x0670 | 0x06D6..=0x06DC | 0x06DF..=0x06E4
+        | 0x06E7..=0x06E8 | 0x06EA..=0x06ED // Arabic marks
+        | 0x0E31 | 0x0E34..=0x0E3A | 0x0E47..=0x0E4E // Thai marks
+        | 0x1AB0..=0x1AFF // Combining Diacritical Marks Extended
+        | 0x1DC0..=0x1DFF // Combining Diacritical Marks Supplement
+        | 0x200B..=0x200F // Zero width space, joiners and direction marks
+        | 0x2060..=0x2064 // Word joiner and invisible operators
+        | 0x20D0..=0x20FF // Combining Diacritical Marks for Symbols
+        | 0x302A..=0x302F | 0x3099..=0x309A // CJK and kana marks
+        | 0xFE00..=0xFE0F // Variation selectors
+        | 0xFE20..=0xFE2F // Combining Half Marks
+    )
+}
+
+/// Check if a zero width character has no visible glyph at all
+fn is_invisible(c: char) -> bool {
+    matches!(c as u32, 0x200B..=0x200F | 0x2060..=0x2064 | 0xFE00..=0xFE0F)
+}
+
+/// Split text into the clusters that each take one cell
+#[cfg(feature = "grapheme")]
+pub fn clusters(text: &str) -> impl Iterator<Item = &str> {
+    unicode_segmentation::UnicodeSegmentation::graphemes(text, true)
+}
+
+/// Split text into the clusters that each take one cell.
+/// Without the `grapheme` feature a cluster is a character followed by its combining marks.
+#[cfg(not(feature = "grapheme"))]
+pub fn clusters(text: &str) -> impl Iterator<Item = &str> {
+    let mut rest = text;
+    core::iter::from_fn(move || {
+        let mut chars = rest.char_indices();
+        let (_, first) = chars.next()?;
+        let end = chars
+            .find(|&(i, c)| !(is_combining(c) || (first == '\r' && c == '\n' && i == 1)))
+            .map_or(rest.len(), |(i, _)| i);
+        let (cluster, tail) = rest.split_at(end);
+        rest = tail;
+        Some(cluster)
+    })
+}
+
+fn is_newline(cluster: &str) -> bool {
+    cluster == "\n" || cluster == "\r\n"
+}
+
+/// A rasterized cluster, positioned relative to the pen on the top of the line
+pub(crate) struct ClusterMask {
+    pub(crate) left: i32,
+    pub(crate) top: i32,
+    pub(crate) advance: u32,
+    pub(crate) mask: Mask,
+}
+
+/// Draw underline and strikethrough over the cell at (x, y) of the mask
+fn decorate(mask: &mut Mask, x: u32, y: u32, advance: u32, style: &TextStyle) {
+    let height = style.line_height();
+    let thickness = style.line_thickness();
+    let mut line = |row: u32| {
+        for y in y + row..cmp::min(y + row + thickness, y + height) {
+            for x in x..x + advance {
+                mask.data[(y * mask.w + x) as usize] = 255;
+            }
+        }
+    };
+    if style.underline {
+        line(height * 14 / GLYPH_HEIGHT);
+    }
+    if style.strikethrough {
+        line(height * 8 / GLYPH_HEIGHT);
+    }
+}
+
+/// Rasterize a cluster with its combining marks stacked on the base glyph,
+/// falling back to the replacement character for characters the font does not have
+pub(crate) fn rasterize_cluster(
+    font: &dyn Font,
+    cluster: &str,
+    style: &TextStyle,
+) -> Option<ClusterMask> {
+    let glyphs: Vec<RasterGlyph> = cluster
+        .chars()
+        .filter(|&c| !is_invisible(c))
+        .filter_map(|c| {
+            font.rasterize(c, style)
+                .or_else(|| font.rasterize(font::REPLACEMENT, style))
+        })
+        .collect();
+    let advance = glyphs.first()?.advance;
+
+    // The cell always covers the line height and advance, so decorations fit in it
+    let (mut left, mut top) = (0, 0);
+    let (mut right, mut bottom) = (advance as i32, style.line_height() as i32);
+    for glyph in glyphs.iter() {
+        left = cmp::min(left, glyph.left);
+        top = cmp::min(top, glyph.top);
+        right = cmp::max(right, glyph.left + glyph.width as i32);
+        bottom = cmp::max(bottom, glyph.top + glyph.height as i32);
+    }
+
+    let mut mask = Mask::new((right - left) as u32, (bottom - top) as u32);
+    for glyph in glyphs.iter() {
+        let x = (glyph.left - left) as usize;
+        let y = (glyph.top - top) as usize;
+        for (row, coverage) in glyph
+            .coverage
+            .chunks_exact(cmp::max(glyph.width, 1) as usize)
+            .take(glyph.height as usize)
+            .enumerate()
+        {
+            let start = (y + row) * mask.w as usize + x;
+            for (old, new) in mask.data[start..start + coverage.len()]
+                .iter_mut()
+                .zip(coverage.iter())
+            {
+                *old = cmp::max(*old, *new);
+            }
+        }
+    }
+    decorate(&mut mask, -left as u32, -top as u32, advance, style);
+
+    Some(ClusterMask {
+        left,
+        top,
+        advance,
+        mask,
+    })
+}
+
+impl TextStyle {
+    /// Size in pixels of the text as drawn by [`Renderer::text`], measuring the widest line
+    pub fn measure(&self, text: &str) -> (u32, u32) {
+        measure(&Unifont, text, self)
+    }
+}
+
+/// Size in pixels of the text as drawn by [`Renderer::text_font`], measuring the widest line
+pub fn measure(font: &dyn Font, text: &str, style: &TextStyle) -> (u32, u32) {
+    let (mut width, mut lines, mut line_width) = (0, 1, 0);
+    for cluster in clusters(text) {
+        if is_newline(cluster) {
+            width = cmp::max(width, line_width);
+            line_width = 0;
+            lines += 1;
+        } else if let Some(cluster) = rasterize_cluster(font, cluster, style) {
+            line_width += cluster.advance;
+        }
+    }
+    width = cmp::max(width, line_width);
+    (width, lines * style.line_height())
+}
+
+/// Draw text from the top left of its first line, returning the bounds of the pixels drawn
+pub(crate) fn draw_text<R: Renderer + ?Sized>(
+    renderer: &mut R,
+    x: i32,
+    y: i32,
+    text: &str,
+    color: Color,
+    font: &dyn Font,
+    style: &TextStyle,
+) -> Rect {
+    let mut masks = Vec::new();
+    let (mut cx, mut cy) = (x, y);
+    for cluster in clusters(text) {
+        if is_newline(cluster) {
+            cx = x;
+            cy += style.line_height() as i32;
+            continue;
+        }
+        if let Some(cluster) = rasterize_cluster(font, cluster, style) {
+            masks.push((cx + cluster.left, cy + cluster.top, cluster.mask));
+            cx += cluster.advance as i32;
+        }
+    }
+    draw_masks(renderer, &masks, color, style)
+}
+
+#[cfg(all(test, feature = "unifont"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blank_glyphs_are_not_missing() {
+        let style = TextStyle::default();
+        // Hangul half-width filler, drawn blank but present in the font
+        assert!(Unifont.rasterize('\u{FFA0}', &style).is_some());
+        // Private use, not in the font
+        assert!(Unifont.rasterize('\u{E000}', &style).is_none());
+    }
+
+    #[test]
+    fn style_measure_matches_unifont() {
+        let style = TextStyle::default();
+        let text = "Hello,\nworld \u{E000}";
+        assert_eq!(style.measure(text), measure(&Unifont, text, &style));
+    }
+}
@@ -0,0 +1,99 @@
+// SPDX-License-Identifier: MIT
+
+//! 2D affine transforms, used to draw images scaled, rotated or sheared.
+
+/// An affine transform mapping (x, y) to (a * x + c * y + e, b * x + d * y + f)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Affine {
+    pub a: f32,
+    pub b: f32,
+    pub c: f32,
+    pub d: f32,
+    pub e: f32,
+    pub f: f32,
+}
+
+impl Default for Affine {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Affine {
+    /// The transform that changes nothing
+    pub const IDENTITY: Affine = Affine {
+        a: 1.0,
+        b: 0.0,
+        c: 0.0,
+        d: 1.0,
+        e: 0.0,
+        f: 0.0,
+    };
+
+    pub const fn new(a: f32, b: f32, c: f32, d: f32, e: f32, f: f32) -> Self {
+        Affine { a, b, c, d, e, f }
+    }
+
+    pub const fn translate(x: f32, y: f32) -> Self {
+        Affine::new(1.0, 0.0, 0.0, 1.0, x, y)
+    }
+
+    pub const fn scale(x: f32, y: f32) -> Self {
+        Affine::new(x, 0.0, 0.0, y, 0.0, 0.0)
+    }
+
+    /// Rotate clockwise on screen, as the y axis points down, by the given sine and cosine
+    /// of the angle. This works without `std`, which is needed to compute them.
+    pub const fn rotate_sin_cos(sin: f32, cos: f32) -> Self {
+        Affine::new(cos, sin, -sin, cos, 0.0, 0.0)
+    }
+
+    /// Rotate clockwise on screen, as the y axis points down, by an angle in radians
+    #[cfg(feature = "std")]
+    pub fn rotate(radians: f32) -> Self {
+        let (sin, cos) = radians.sin_cos();
+        Self::rotate_sin_cos(sin, cos)
+    }
+
+    /// Shear by the tangents of the angles from the axes
+    pub const fn shear(x: f32, y: f32) -> Self {
+        Affine::new(1.0, y, x, 1.0, 0.0, 0.0)
+    }
+
+    /// The transform that applies `self` and then `next`
+    pub fn then(&self, next: &Affine) -> Self {
+        Affine {
+            a: next.a * self.a + next.c * self.b,
+            b: next.b * self.a + next.d * self.b,
+            c: next.a * self.c + next.c * self.d,
+            d: next.b * self.c + next.d * self.d,
+            e: next.a * self.e + next.c * self.f + next.e,
+            f: next.b * self.e + next.d * self.f + next.f,
+        }
+    }
+
+    /// The transform that undoes this one, `None` if it collapses the plane to a line or point
+    pub fn invert(&self) -> Option<Self> {
+        let det = self.a * self.d - self.b * self.c;
+        if det.abs() < f32::EPSILON {
+            return None;
+        }
+        let (a, b, c, d) = (self.d / det, -self.b / det, -self.c / det, self.a / det);
+        Some(Affine {
+            a,
+            b,
+            c,
+            d,
+            e: -(a * self.e + c * self.f),
+            f: -(b * self.e + d * self.f),
+        })
+    }
+
+    /// Transform a point
+    pub fn apply(&self, x: f32, y: f32) -> (f32, f32) {
+        (
+            self.a * x + self.c * y + self.e,
+            self.b * x + self.d * y + self.f,
+        )
+    }
+}
@@ -0,0 +1,367 @@
+// SPDX-License-Identifier: MIT
+
+//! Filling triangles and meshes with colors interpolated between their vertices, or with a
+//! texture mapped onto them.
+
+use core::cmp;
+
+use crate::color::Color;
+use crate::image::ImageRoi;
+use crate::math;
+use crate::renderer::Renderer;
+use crate::resample::{self, Filter};
+
+/// Fractional bits of vertex positions, which are snapped to 1/256 of a pixel so that
+/// triangles sharing an edge fill each pixel along it exactly once
+const SUBPIXEL_BITS: u32 = 8;
+const SUBPIXEL: f32 = (1 << SUBPIXEL_BITS) as f32;
+
+/// Positions are clamped to this many pixels from the origin, to keep edge tests in range
+const MAX_COORDINATE: f32 = (1 << 20) as f32;
+
+/// A corner of a triangle
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Vertex {
+    pub x: f32,
+    pub y: f32,
+    /// Interpolated across the triangle, and multiplied with the texture when there is one
+    pub color: Color,
+    /// Texture coordinates, in pixels of the texture
+    pub u: f32,
+    pub v: f32,
+    /// The homogeneous `w` of the vertex before projection, used by `Mapping::Perspective`
+    pub w: f32,
+}
+
+impl Vertex {
+    /// A vertex with a color, for filling without a texture
+    pub const fn new(x: f32, y: f32, color: Color) -> Self {
+        Vertex {
+            x,
+            y,
+            color,
+            u: 0.0,
+            v: 0.0,
+            w: 1.0,
+        }
+    }
+
+    /// A vertex with texture coordinates, showing the texture unchanged
+    pub const fn textured(x: f32, y: f32, u: f32, v: f32) -> Self {
+        Vertex {
+            x,
+            y,
+            color: Color::rgba(255, 255, 255, 255),
+            u,
+            v,
+            w: 1.0,
+        }
+    }
+}
+
+/// How texture coordinates are interpolated across a triangle
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Mapping {
+    /// Linearly on the screen, which is exact for flat, unprojected triangles
+    #[default]
+    Affine,
+    /// Linearly before projection, using the `w` of each vertex, so that textures on
+    /// triangles seen at an angle do not bend. Triangles with a vertex at `w <= 0` are skipped.
+    Perspective,
+}
+
+/// A texture sampled over a triangle
+pub(crate) struct Texture<'a, 'b> {
+    pub(crate) image: &'a ImageRoi<'b>,
+    pub(crate) filter: Filter,
+    pub(crate) mapping: Mapping,
+}
+
+/// A position in fixed point
+fn snap(vertex: &Vertex) -> [i64; 2] {
+    let fixed = |c: f32| math::round(c.clamp(-MAX_COORDINATE, MAX_COORDINATE) * SUBPIXEL) as i64;
+    [fixed(vertex.x), fixed(vertex.y)]
+}
+
+/// Twice the signed area of (a, b, p), positive when p is clockwise from a to b on screen
+fn edge(a: [i64; 2], b: [i64; 2], p: [i64; 2]) -> i64 {
+    (b[0] - a[0]) * (p[1] - a[1]) - (b[1] - a[1]) * (p[0] - a[0])
+}
+
+/// Whether pixel centers exactly on an edge belong to the triangle: only on top edges,
+/// which are horizontal with the triangle below, and on left edges
+fn owns_edge(a: [i64; 2], b: [i64; 2]) -> bool {
+    (a[1] == b[1] && b[0] > a[0]) || b[1] < a[1]
+}
+
+/// Fill a triangle, interpolating the vertex colors and sampling the texture if there is one.
+/// Triangles with a position that is not a number are skipped.
+pub(crate) fn fill<R: Renderer + ?Sized>(
+    renderer: &mut R,
+    vertices: &[Vertex; 3],
+    texture: Option<&Texture>,
+) {
+    if vertices.iter().any(|vertex| vertex.x.is_nan() || vertex.y.is_nan()) {
+        return;
+    }
+    let mut vertices = *vertices;
+    let mut p = [snap(&vertices[0]), snap(&vertices[1]), snap(&vertices[2])];
+    let mut area = edge(p[0], p[1], p[2]);
+    if area == 0 {
+        return;
+    }
+    // Wind clockwise, so that the inside is on the same side of every edge
+    if area < 0 {
+        vertices.swap(1, 2);
+        p.swap(1, 2);
+        area = -area;
+    }
+    let perspective = matches!(texture, Some(texture) if texture.mapping == Mapping::Perspective);
+    if perspective && vertices.iter().any(|vertex| vertex.w <= 0.0) {
+        return;
+    }
+
+    let fixed_min = |c: usize| cmp::min(cmp::min(p[0][c], p[1][c]), p[2][c]);
+    let fixed_max = |c: usize| cmp::max(cmp::max(p[0][c], p[1][c]), p[2][c]);
+    // Pixels whose centers may be inside
+    let pixel_min = |c: usize| ((fixed_min(c) >> SUBPIXEL_BITS) - 1).max(0) as i32;
+    let left = pixel_min(0);
+    let top = pixel_min(1);
+    let right = cmp::min((fixed_max(0) >> SUBPIXEL_BITS) + 1, renderer.width() as i64) as i32;
+    let bottom = cmp::min(
+        (fixed_max(1) >> SUBPIXEL_BITS) + 1,
+        renderer.height() as i64,
+    ) as i32;
+
+    let edges = [(1, 2), (2, 0), (0, 1)];
+    let owned = edges.map(|(a, b)| owns_edge(p[a], p[b]));
+    let half = 1 << (SUBPIXEL_BITS - 1);
+    for y in top..bottom {
+        for x in left..right {
+            let center = [
+                ((x as i64) << SUBPIXEL_BITS) + half,
+                ((y as i64) << SUBPIXEL_BITS) + half,
+            ];
+            // Each edge gives the weight of the vertex opposite it
+            let mut weights = [0.0; 3];
+            let mut inside = true;
+            for (i, &(a, b)) in edges.iter().enumerate() {
+                let e = edge(p[a], p[b], center);
+                if e < 0 || (e == 0 && !owned[i]) {
+                    inside = false;
+                    break;
+                }
+                weights[i] = e as f32 / area as f32;
+            }
+            if !inside {
+                continue;
+            }
+            if perspective {
+                let mut sum = 0.0;
+                for (weight, vertex) in weights.iter_mut().zip(vertices.iter()) {
+                    *weight /= vertex.w;
+                    sum += *weight;
+                }
+                for weight in weights.iter_mut() {
+                    *weight /= sum;
+                }
+            }
+
+            let mut color = interpolate(&vertices, &weights);
+            if let Some(texture) = texture {
+                let u: f32 = (0..3).map(|i| weights[i] * vertices[i].u).sum();
+                let v: f32 = (0..3).map(|i| weights[i] * vertices[i].v).sum();
+                let sample = resample::sample(texture.image, u, v, texture.filter);
+                color = modulate(sample, color);
+            }
+            renderer.pixel(x, y, color);
+        }
+    }
+}
+
+/// The vertex colors mixed by the weights of the vertices
+fn interpolate(vertices: &[Vertex; 3], weights: &[f32; 3]) -> Color {
+    let channel = |get: fn(&Color) -> u8| {
+        let value: f32 = (0..3)
+            .map(|i| weights[i] * get(&vertices[i].color) as f32)
+            .sum();
+        math::round(value).clamp(0, 255) as u8
+    };
+    Color::rgba(
+        channel(Color::r),
+        channel(Color::g),
+        channel(Color::b),
+        channel(Color::a),
+    )
+}
+
+/// Two colors multiplied channel by channel
+fn modulate(a: Color, b: Color) -> Color {
+    let channel = |x: u8, y: u8| ((x as u32 * y as u32 + 127) / 255) as u8;
+    Color::rgba(
+        channel(a.r(), b.r()),
+        channel(a.g(), b.g()),
+        channel(a.b(), b.b()),
+        channel(a.a(), b.a()),
+    )
+}
+
+/// Fill the triangles of a mesh, given as indices into its vertices. Triangles with an index
+/// past the end of the vertices are skipped.
+pub(crate) fn fill_mesh<R: Renderer + ?Sized>(
+    renderer: &mut R,
+    vertices: &[Vertex],
+    indices: &[[u32; 3]],
+    texture: Option<&Texture>,
+) {
+    for triangle in indices {
+        let vertex = |i: usize| vertices.get(triangle[i] as usize).copied();
+        if let (Some(a), Some(b), Some(c)) = (vertex(0), vertex(1), vertex(2)) {
+            fill(renderer, &[a, b, c], texture);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(not(feature = "std"))]
+    use alloc::{vec, vec::Vec};
+
+    use super::*;
+    use crate::image::Image;
+    use crate::Mode;
+
+    const W: u32 = 12;
+    const H: u32 = 10;
+    const WHITE: Color = Color::rgb(255, 255, 255);
+    const RED: Color = Color::rgb(255, 0, 0);
+    const GREEN: Color = Color::rgb(0, 255, 0);
+    const BLUE: Color = Color::rgb(0, 0, 255);
+
+    fn canvas() -> Image {
+        let image = Image::from_color(W, H, Color::rgba(0, 0, 0, 0));
+        image.mode().set(Mode::Overwrite);
+        image
+    }
+
+    /// How many of the triangles fill each pixel
+    fn counts(vertices: &[Vertex], indices: &[[u32; 3]]) -> Vec<u32> {
+        let mut counts = vec![0; (W * H) as usize];
+        for triangle in indices {
+            let mut image = canvas();
+            fill_mesh(&mut image, vertices, &[*triangle], None);
+            for (count, pixel) in counts.iter_mut().zip(image.data()) {
+                *count += (pixel.a() != 0) as u32;
+            }
+        }
+        counts
+    }
+
+    #[test]
+    fn shared_edges() {
+        // A square with its edges and diagonal through pixel centers, so that only the fill
+        // rule decides who owns them
+        let square = [
+            Vertex::new(1.5, 1.5, WHITE),
+            Vertex::new(5.5, 1.5, WHITE),
+            Vertex::new(5.5, 5.5, WHITE),
+            Vertex::new(1.5, 5.5, WHITE),
+        ];
+        for indices in [[[0, 1, 2], [0, 2, 3]], [[0, 1, 3], [1, 2, 3]]] {
+            let counts = counts(&square, &indices);
+            for y in 0..H {
+                for x in 0..W {
+                    let inside = (1..5).contains(&x) && (1..5).contains(&y);
+                    assert_eq!(counts[(y * W + x) as usize], inside as u32, "({}, {})", x, y);
+                }
+            }
+        }
+
+        // A fan of triangles around a fractional point covers the pixels in it once
+        let mut fan = vec![Vertex::new(6.3, 4.7, WHITE)];
+        let ring = [
+            (1.2, 0.4),
+            (7.9, -0.6),
+            (11.5, 3.25),
+            (10.0, 9.5),
+            (4.75, 8.5),
+            (0.3, 6.1),
+        ];
+        fan.extend(ring.iter().map(|&(x, y)| Vertex::new(x, y, WHITE)));
+        let indices: Vec<[u32; 3]> = (1..=6).map(|i| [0, i, i % 6 + 1]).collect();
+        let counts = counts(&fan, &indices);
+        assert!(counts.iter().all(|&count| count <= 1));
+        for &(x, y) in &[(6, 4), (5, 4), (6, 5), (3, 3), (9, 6), (7, 1)] {
+            assert_eq!(counts[(y * W + x) as usize], 1, "({}, {})", x, y);
+        }
+    }
+
+    #[test]
+    fn gouraud() {
+        let corners = [(0.5, 0.5), (8.5, 0.5), (0.5, 8.5)];
+        let colors = [RED, GREEN, BLUE];
+        for first in 0..3 {
+            let vertex = |i: usize| {
+                let (x, y) = corners[i];
+                Vertex::new(x, y, colors[(first + i) % 3])
+            };
+            let mut image = canvas();
+            fill(&mut image, &[vertex(0), vertex(1), vertex(2)], None);
+
+            // The top left corner is on owned edges, the others on the unowned diagonal
+            assert_eq!(image.getpixel(0, 0).data, colors[first].data);
+            assert_eq!(image.getpixel(8, 0).a(), 0);
+            assert_eq!(image.getpixel(0, 8).a(), 0);
+
+            // Half of the first corner and a quarter of each other one
+            let mixed = image.getpixel(2, 2);
+            let mut expected = [64; 3];
+            expected[first] = 128;
+            assert_eq!([mixed.r(), mixed.g(), mixed.b()], expected);
+            assert_eq!(mixed.a(), 255);
+        }
+    }
+
+    #[test]
+    fn degenerate() {
+        let white = |x, y| Vertex::new(x, y, WHITE);
+        let triangles = [
+            // Collinear and coincident points
+            [white(1.0, 1.0), white(5.0, 5.0), white(9.0, 9.0)],
+            [white(3.0, 3.0), white(3.0, 3.0), white(8.0, 2.0)],
+            [white(4.0, 4.0), white(4.0, 4.0), white(4.0, 4.0)],
+            // Closer than the snapping precision
+            [white(2.0, 2.0), white(2.001, 2.0), white(2.0, 2.001)],
+            // Off screen
+            [white(-9.0, -9.0), white(-1.0, -9.0), white(-9.0, -1.0)],
+            [white(13.0, 0.0), white(20.0, 0.0), white(13.0, 9.0)],
+            [white(0.0, 11.0), white(9.0, 11.0), white(0.0, 30.0)],
+            [white(1e30, 1e30), white(2e30, 1e30), white(1e30, 2e30)],
+            // Not a number
+            [white(f32::NAN, 0.0), white(5.0, 0.0), white(0.0, 5.0)],
+        ];
+        for triangle in triangles.iter() {
+            let mut image = canvas();
+            fill(&mut image, triangle, None);
+            assert!(image.data().iter().all(|pixel| pixel.a() == 0), "{:?}", triangle);
+        }
+
+        // Indices past the end of the vertices are skipped
+        let mut image = canvas();
+        fill_mesh(&mut image, &triangles[5], &[[0, 1, 3], [u32::MAX, 0, 1]], None);
+        assert!(image.data().iter().all(|pixel| pixel.a() == 0));
+    }
+
+    #[test]
+    fn clipped() {
+        // Much larger than the renderer, with vertices beyond the clamped range
+        let mut image = canvas();
+        let triangle = [
+            Vertex::new(-1e9, -1e9, WHITE),
+            Vertex::new(1e9, -1.0, WHITE),
+            Vertex::new(-1.0, 1e9, WHITE),
+        ];
+        fill(&mut image, &triangle, None);
+        assert!(image.data().iter().all(|pixel| pixel.data == WHITE.data));
+    }
+}